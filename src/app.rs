@@ -1,11 +1,20 @@
+use crate::bookmarks::BookmarkStore;
+use crate::buffer::Buffer;
+use crate::debug::DebugOverlay;
+use crate::demo::DemoTimeline;
+use crate::dither::Dither;
+use crate::history::HistoryDb;
 use crate::i18n::t;
+use crate::playlist::Playlist;
+use crate::session::{BufferState, Session};
 use crate::text::{
     clamp_cursor, delete_next_grapheme, delete_prev_grapheme, delete_word, insert_grapheme,
     move_left_grapheme, move_right_grapheme, move_word_backward, move_word_forward,
 };
+use crate::theme::Theme;
 use crate::types::*;
-use crate::utils::log_msg;
-use crate::video::VideoEngine;
+use crate::utils::{clear_log, log_msg, parse_timestamp};
+use crate::video::{VideoEngine, VideoOptions};
 use crate::web::WebEngine;
 use crossterm::event::{KeyCode, KeyModifiers};
 use rand::prelude::IndexedRandom;
@@ -15,63 +24,92 @@ use std::sync::{
     Arc,
     mpsc::{self, Receiver},
 };
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+const MAX_COMPLETIONS: usize = 8;
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// A drag-resize sends a burst of events; the pipeline restarts once they settle.
+const RESIZE_SETTLE: Duration = Duration::from_millis(150);
+/// Steps for `[` and `]`; ffmpeg's atempo accepts down to 0.5.
+const PLAYBACK_RATES: [f64; 6] = [0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
 
 pub struct App {
     pub mode: AppMode,
     pub previous_mode: AppMode,
     pub render_mode: RenderMode,
+    pub dither: Dither,
+    pub themes: Vec<Theme>,
+    pub theme_index: usize,
 
     web: WebEngine,
     rx: Receiver<BgEvent>,
-    pub is_loading: bool,
-
-    pub prefetch_data: Option<BgEvent>,
 
-    pub current_url: String,
-    pub url_input: String,
-    pub cursor_pos: usize,
-    pub page_text: Arc<String>,
-    pub dense_text: Arc<Vec<char>>,
+    pub buffers: Vec<Buffer>,
+    pub active_buffer: usize,
+    next_buffer_id: usize,
 
-    pub link_map: Arc<HashMap<String, String>>,
     pub hint_buffer: String,
     pub hint_mode_active: bool,
-    pub valid_links: Arc<Vec<String>>,
+    pub pending_mark: Option<char>,
+
+    pub prompt: Prompt,
+    pub prompt_input: String,
+    pub prompt_cursor: usize,
+
+    pub bookmarks: BookmarkStore,
+    pub bookmark_selected: usize,
 
-    pub history: Vec<String>,
-    pub history_index: usize,
-    pub scroll_y: u16,
+    pub history_db: HistoryDb,
+    /// URL completions for the Insert mode input as `(url, title)`, best first.
+    pub completions: Vec<(String, String)>,
+    pub completion_selected: usize,
 
     pub auto_scroll: AutoScroll,
     pub scroll_speed_multiplier: f32,
     pub last_scroll_tick: Instant,
 
     pub engine: VideoEngine,
+    pub playlist: Playlist,
 
-    pub demo: Vec<ScriptEntry>,
-    pub demo_index: usize,
+    /// Demo script of the current playlist item
+    pub demo: DemoTimeline,
     pub last_prefetch_index: Option<usize>,
-    pub demo_cache: HashMap<
-        String,
-        (
-            Arc<String>,
-            Arc<Vec<char>>,
-            Arc<HashMap<String, String>>,
-            Arc<Vec<String>>,
-        ),
-    >,
+    /// Prefetched demo pages with the images still to fetch each time one is shown
+    pub demo_cache: HashMap<String, (CachedPage, Vec<ImageRef>)>,
     pub autoplay: bool,
+    pub loop_a: Option<f64>,
+    pub loop_b: Option<f64>,
+    pending_resize: Option<(u16, u16, Instant)>,
+    pub debug: DebugOverlay,
+
+    session_name: String,
+    last_session_save: Instant,
+}
+
+/// How the video mask starts out looking; both can be switched while playing.
+pub struct MaskOptions {
+    pub dither: Dither,
+    /// Built-in and user themes, never empty
+    pub themes: Vec<Theme>,
+    pub theme_index: usize,
 }
 
 impl App {
-    pub fn new(video_path: String, start_url: String, demo: Vec<ScriptEntry>) -> Self {
-        let _ = std::fs::write("bad-browser.log", "");
+    pub fn new(
+        playlist: Playlist,
+        start_url: String,
+        session_name: String,
+        restore: Option<Session>,
+        video: VideoOptions,
+        mask: MaskOptions,
+    ) -> Self {
+        clear_log();
         log_msg("info", "App initialized");
 
         let (tx, rx) = mpsc::sync_channel(5);
         let web = WebEngine::new(tx.clone());
-        let engine = VideoEngine::new(video_path, tx);
+        let engine = VideoEngine::new(&playlist.current().video, &video, tx);
+        let demo = DemoTimeline::new(playlist.current().demo.clone());
 
         let duration = engine.duration;
         log_msg("info", &format!("Video Duration: {duration:.2}s"));
@@ -80,37 +118,55 @@ impl App {
             mode: AppMode::Normal,
             previous_mode: AppMode::Normal,
             render_mode: RenderMode::Cast,
+            dither: mask.dither,
+            themes: mask.themes,
+            theme_index: mask.theme_index,
             web,
             rx,
-            is_loading: false,
-            prefetch_data: None,
-            current_url: start_url.clone(),
-            url_input: start_url.clone(),
-            cursor_pos: start_url.len(),
-            page_text: Arc::new(String::new()),
-            dense_text: Arc::new(Vec::new()),
-            link_map: Arc::new(HashMap::new()),
+            buffers: vec![Buffer::new(0, start_url.clone())],
+            active_buffer: 0,
+            next_buffer_id: 1,
             hint_buffer: String::new(),
             hint_mode_active: false,
-            valid_links: Arc::new(Vec::new()),
-            history: vec![],
-            history_index: 0,
-            scroll_y: 0,
+            pending_mark: None,
+            prompt: Prompt::Url,
+            prompt_input: String::new(),
+            prompt_cursor: 0,
+            bookmarks: BookmarkStore::load(),
+            bookmark_selected: 0,
+            history_db: HistoryDb::load(),
+            completions: Vec::new(),
+            completion_selected: 0,
             auto_scroll: AutoScroll::Off,
             scroll_speed_multiplier: 1.0,
             last_scroll_tick: Instant::now(),
             engine,
-            demo_index: 0,
+            playlist,
             last_prefetch_index: None,
             demo_cache: HashMap::new(),
             demo,
             autoplay: false,
+            loop_a: None,
+            loop_b: None,
+            pending_resize: None,
+            debug: DebugOverlay::default(),
+            session_name,
+            last_session_save: Instant::now(),
         };
 
-        app.trigger_fetch(start_url, false, false);
+        if let Some(session) = restore {
+            app.restore_session(session);
+        } else {
+            app.trigger_fetch(start_url, false, false);
+        }
 
-        // Preload ALL demo pages for instant transitions
-        let demo_urls: Vec<String> = app.demo.iter().map(|e| e.url.clone()).collect();
+        // Preload ALL demo pages, of every video, for instant transitions
+        let demo_urls: Vec<String> = app
+            .playlist
+            .items()
+            .iter()
+            .flat_map(|item| item.demo.iter().map(|e| e.url.clone()))
+            .collect();
         for url in demo_urls {
             app.trigger_fetch(url, true, false);
         }
@@ -118,103 +174,315 @@ impl App {
         app
     }
 
+    fn restore_session(&mut self, session: Session) {
+        log_msg(
+            "info",
+            &format!(
+                "Restoring session {}: {} buffers",
+                self.session_name,
+                session.buffers.len()
+            ),
+        );
+        self.render_mode = session.render_mode;
+        self.engine.set_cell_pixels(self.render_mode.cell_pixels());
+        self.scroll_speed_multiplier = session.scroll_speed_multiplier;
+        // Demo scrolling only makes sense while the video is playing
+        self.auto_scroll = match session.auto_scroll {
+            AutoScroll::Demo => AutoScroll::Off,
+            other => other,
+        };
+
+        self.buffers.clear();
+        for (id, state) in session.buffers.into_iter().enumerate() {
+            let mut buffer = Buffer::new(id, state.url.clone());
+            let has_history = !state.history.is_empty();
+            buffer.history_index = state
+                .history_index
+                .min(state.history.len().saturating_sub(1));
+            buffer.history = state.history;
+            buffer.pending_scroll = Some(state.scroll_y);
+            self.buffers.push(buffer);
+
+            self.active_buffer = id;
+            self.trigger_fetch(state.url, false, has_history);
+        }
+        self.next_buffer_id = self.buffers.len();
+        self.active_buffer = session.active_buffer.min(self.buffers.len() - 1);
+    }
+
+    pub fn snapshot(&self) -> Session {
+        Session {
+            buffers: self
+                .buffers
+                .iter()
+                .map(|b| BufferState {
+                    url: b.current_url.clone(),
+                    history: b.history.clone(),
+                    history_index: b.history_index,
+                    scroll_y: b.scroll_y,
+                })
+                .collect(),
+            active_buffer: self.active_buffer,
+            render_mode: self.render_mode,
+            locale: rust_i18n::locale(),
+            auto_scroll: self.auto_scroll,
+            scroll_speed_multiplier: self.scroll_speed_multiplier,
+        }
+    }
+
+    pub fn save_session(&mut self) {
+        self.last_session_save = Instant::now();
+        if let Err(e) = self.snapshot().save(&self.session_name) {
+            log_msg("error", &format!("Session: failed to save: {e}"));
+        }
+    }
+
+    pub fn autosave_session(&mut self) {
+        if self.last_session_save.elapsed() >= SESSION_SAVE_INTERVAL {
+            self.save_session();
+        }
+    }
+
+    pub fn on_resize(&mut self, term_w: u16, term_h: u16) {
+        self.pending_resize = Some((term_w, term_h, Instant::now()));
+    }
+
+    /// Re-decodes the video at the new terminal size once resizing has settled.
+    pub fn apply_resize(&mut self) {
+        if let Some((w, h, at)) = self.pending_resize
+            && at.elapsed() >= RESIZE_SETTLE
+        {
+            self.pending_resize = None;
+            self.engine.resize(w as usize, h as usize);
+        }
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.themes[self.theme_index]
+    }
+
+    /// Feeds the debug overlay's rates after each redraw.
+    pub fn record_draw(&mut self) {
+        self.debug.on_draw(self.engine.clock.stats().decoded);
+    }
+
+    /// Page and image downloads still running, for the debug overlay.
+    pub fn pending_fetches(&self) -> usize {
+        self.web.pending()
+    }
+
+    /// Demo entries whose page is already cached, for the debug overlay.
+    pub fn demo_cached(&self) -> usize {
+        self.demo
+            .entries()
+            .iter()
+            .filter(|e| self.demo_cache.contains_key(&e.url))
+            .count()
+    }
+
+    /// Whether the video mask is on screen; it stays up while typing a go-to time.
+    pub fn video_visible(&self) -> bool {
+        self.mode == AppMode::Video
+            || (self.mode == AppMode::Insert && self.prompt == Prompt::GotoTime)
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffers[self.active_buffer]
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.active_buffer]
+    }
+
+    fn buffer_by_id_mut(&mut self, id: usize) -> Option<&mut Buffer> {
+        self.buffers.iter_mut().find(|b| b.id == id)
+    }
+
     pub fn trigger_fetch(&mut self, url: String, is_prefetch: bool, is_history: bool) {
+        let buffer = &mut self.buffers[self.active_buffer];
         if !is_prefetch {
-            self.is_loading = true;
-            self.url_input = url.clone();
-            self.cursor_pos = self.url_input.len();
+            buffer.is_loading = true;
+            buffer.url_input = url.clone();
+            buffer.cursor_pos = buffer.url_input.len();
         }
         self.web
-            .fetch(&self.current_url, url, is_prefetch, is_history);
+            .fetch(buffer.id, &buffer.current_url, url, is_prefetch, is_history);
     }
 
-    pub fn handle_events(&mut self) {
+    pub fn open_buffer(&mut self, url: String) {
+        let id = self.next_buffer_id;
+        self.next_buffer_id += 1;
+        self.buffers
+            .insert(self.active_buffer + 1, Buffer::new(id, url.clone()));
+        self.active_buffer += 1;
+        log_msg("info", &format!("Buffer {id} opened"));
+
+        if !url.is_empty() {
+            self.trigger_fetch(url, false, false);
+        }
+    }
+
+    pub fn close_buffer(&mut self) {
+        if self.buffers.len() <= 1 {
+            return;
+        }
+        let closed = self.buffers.remove(self.active_buffer);
+        log_msg("info", &format!("Buffer {} closed", closed.id));
+        if self.active_buffer >= self.buffers.len() {
+            self.active_buffer = self.buffers.len() - 1;
+        }
+    }
+
+    pub fn switch_buffer(&mut self, delta: isize) {
+        let len = self.buffers.len() as isize;
+        self.active_buffer = (self.active_buffer as isize + delta).rem_euclid(len) as usize;
+    }
+
+    pub fn move_buffer(&mut self, delta: isize) {
+        let target = self.active_buffer as isize + delta;
+        if target < 0 || target >= self.buffers.len() as isize {
+            return;
+        }
+        self.buffers.swap(self.active_buffer, target as usize);
+        self.active_buffer = target as usize;
+    }
+
+    /// Applies the background events that have arrived, returning whether there were any.
+    pub fn handle_events(&mut self) -> bool {
+        let mut handled = false;
         while let Ok(event) = self.rx.try_recv() {
+            handled = true;
             match event {
                 BgEvent::PageLoaded {
+                    buffer_id,
                     url,
+                    title,
                     text,
                     dense_text,
                     link_map,
                     links,
+                    images,
                     is_history_nav,
                 } => {
                     log_msg("info", "Page Loaded");
-                    self.is_loading = false;
-                    self.current_url = url.clone();
-                    self.url_input = url.clone();
-                    self.cursor_pos = self.url_input.len();
-                    self.page_text = Arc::new(text);
-                    self.dense_text = Arc::new(dense_text);
-                    self.link_map = Arc::new(link_map);
-                    self.valid_links = Arc::new(links);
-                    self.scroll_y = 0;
+                    let is_active = self.buffer().id == buffer_id;
+                    self.history_db.record_visit(&url, &title);
+                    let Some(buffer) = self.buffer_by_id_mut(buffer_id) else {
+                        continue;
+                    };
+                    buffer.is_loading = false;
+                    buffer.show_page(
+                        url.clone(),
+                        title,
+                        (
+                            Arc::new(text),
+                            Arc::new(dense_text),
+                            Arc::new(link_map),
+                            Arc::new(links),
+                        ),
+                    );
+
+                    if let Some(scroll_y) = buffer.pending_scroll.take() {
+                        buffer.scroll_y = scroll_y;
+                    }
 
                     if !is_history_nav {
-                        if self.history.last() != Some(&url) {
-                            self.history.truncate(self.history_index + 1);
-                            self.history.push(url);
-                            self.history_index = self.history.len() - 1;
-                        }
+                        buffer.push_history(url.clone());
                     }
 
-                    self.prefetch_data = None;
-                    if self.auto_scroll == AutoScroll::RandomWalk {
+                    buffer.prefetch_data = None;
+                    self.fetch_images(buffer_id, &url, images);
+                    if is_active && self.auto_scroll == AutoScroll::RandomWalk {
                         self.trigger_random_prefetch();
                     }
                 }
                 BgEvent::PrefetchReady {
+                    buffer_id,
                     url,
+                    title,
                     text,
                     dense_text,
                     link_map,
                     links,
+                    images,
                 } => {
-                    // Store in demo cache if it's a demo URL
-                    if self.demo.iter().any(|e| e.url == url) {
-                        self.demo_cache.insert(
-                            url,
-                            (
-                                Arc::new(text),
-                                Arc::new(dense_text),
-                                Arc::new(link_map),
-                                Arc::new(links),
-                            ),
+                    // Store in demo cache if it's a demo URL. dense_text already has captions
+                    // for images; text keeps the markers their art replaces.
+                    if self.is_demo_url(&url) {
+                        let page = (
+                            Arc::new(text),
+                            Arc::new(dense_text),
+                            Arc::new(link_map),
+                            Arc::new(links),
                         );
+                        self.demo_cache.insert(url, (page, images));
                         log_msg("info", "Demo: Cached page");
-                    } else {
-                        self.prefetch_data = Some(BgEvent::PrefetchReady {
+                    } else if let Some(buffer) = self.buffer_by_id_mut(buffer_id) {
+                        buffer.prefetch_data = Some(BgEvent::PrefetchReady {
+                            buffer_id,
                             url,
+                            title,
                             text,
                             dense_text,
                             link_map,
                             links,
+                            images,
                         });
                     }
                 }
+                BgEvent::ImageReady {
+                    buffer_id,
+                    page_url,
+                    index,
+                    art,
+                    alt,
+                } => {
+                    let Some(buffer) = self.buffer_by_id_mut(buffer_id) else {
+                        continue;
+                    };
+                    if buffer.current_url != page_url {
+                        continue;
+                    }
+                    let mut block = art.join("\n");
+                    if !alt.is_empty() {
+                        block = format!("{block}\n[{alt}]");
+                    }
+                    // dense_text is left alone so the video mask keeps flowing prose, not art
+                    let text = buffer.page_text.replace(&image_marker(index), &block);
+                    buffer.page_text = Arc::new(text);
+                }
                 BgEvent::VideoEnded(id) => {
                     if self.mode == AppMode::Video && id == self.engine.session_id {
                         log_msg("info", "Video Ended Naturally");
-                        if self.autoplay && !self.demo.is_empty() {
-                            log_msg("info", "Autoplay: Restarting video");
-                            self.demo_index = 0;
-                            let term_w = *self.engine.source_width.lock().unwrap();
-                            let term_h = *self.engine.source_height.lock().unwrap();
+                        if self.playlist.advance() {
+                            self.play_current_item();
+                        } else if self.autoplay {
+                            // advance() went back to the first video
+                            log_msg("info", "Autoplay: Restarting playlist");
+                            if self.playlist.len() > 1 {
+                                self.load_current_item();
+                            }
+                            let (term_w, term_h) = self.engine.term_size;
                             self.engine.start(term_w, term_h, 0.0);
-                            self.apply_demo_page(0);
-                            self.demo_index = 1;
+                            self.begin_demo();
                         } else {
                             self.stop_video();
+                            if self.playlist.len() > 1 {
+                                self.load_current_item();
+                            }
                         }
                     }
                 }
-                BgEvent::Error(e) => {
-                    log_msg("error", &format!("{e}"));
-                    self.is_loading = false;
-                    self.page_text = Arc::new(t!("errors.generic", error = e));
+                BgEvent::Error(buffer_id, e) => {
+                    log_msg("error", &e);
+                    if let Some(buffer) = self.buffer_by_id_mut(buffer_id) {
+                        buffer.is_loading = false;
+                        buffer.page_text = Arc::new(t!("errors.generic", error = e));
+                    }
                 }
             }
         }
+        handled
     }
 
     pub fn on_key(
@@ -226,6 +494,7 @@ impl App {
     ) -> bool {
         match self.mode {
             AppMode::Insert => self.handle_insert(key, modifiers),
+            AppMode::Bookmarks => self.handle_bookmarks(key),
             _ => {
                 if self.hint_mode_active {
                     match key {
@@ -238,7 +507,7 @@ impl App {
                         }
                         KeyCode::Char(c) => {
                             self.hint_buffer.push(c);
-                            if let Some(url) = self.link_map.get(&self.hint_buffer) {
+                            if let Some(url) = self.buffer().link_map.get(&self.hint_buffer) {
                                 let u = url.clone();
                                 self.hint_mode_active = false;
                                 self.hint_buffer.clear();
@@ -253,7 +522,17 @@ impl App {
                     return false;
                 }
 
+                if let Some(mark) = self.pending_mark.take() {
+                    if let KeyCode::Char(c) = key
+                        && c.is_ascii_alphabetic()
+                    {
+                        self.handle_quickmark(mark, c);
+                    }
+                    return false;
+                }
+
                 match key {
+                    KeyCode::F(3) => self.debug.visible = !self.debug.visible,
                     KeyCode::Char('q') => {
                         if self.mode == AppMode::Video {
                             self.stop_video();
@@ -261,10 +540,7 @@ impl App {
                             return true;
                         }
                     }
-                    KeyCode::Char('i') => {
-                        self.previous_mode = self.mode;
-                        self.mode = AppMode::Insert;
-                    }
+                    KeyCode::Char('i') => self.open_prompt(Prompt::Url),
                     KeyCode::Char('p') => {
                         let is_running = self.engine.current_stopper.is_some();
                         if self.mode == AppMode::Video {
@@ -281,44 +557,48 @@ impl App {
                         } else if !is_running {
                             self.engine.start(term_w as usize, term_h as usize, 0.0);
                             self.mode = AppMode::Video;
-                            if !self.demo.is_empty() {
-                                self.auto_scroll = AutoScroll::Demo;
-                                self.demo_index = 0;
-                                log_msg(
-                                    "info",
-                                    &format!(
-                                        "Demo: {} entries, {} cached",
-                                        self.demo.len(),
-                                        self.demo_cache.len()
-                                    ),
-                                );
-                                self.apply_demo_page(0);
-                                self.demo_index = 1;
-                            }
+                            self.begin_demo();
                         }
                     }
                     KeyCode::Char(' ') if self.mode == AppMode::Video => self.engine.toggle_pause(),
 
+                    KeyCode::Char('M') => self.pending_mark = Some('M'),
+                    KeyCode::Char('\'') => self.pending_mark = Some('\''),
+                    KeyCode::Char('b') => {
+                        self.previous_mode = self.mode;
+                        self.mode = AppMode::Bookmarks;
+                        self.bookmark_selected = self
+                            .bookmark_selected
+                            .min(self.bookmarks.bookmarks.len().saturating_sub(1));
+                    }
+                    KeyCode::Char('B') => self.open_prompt(Prompt::BookmarkTags),
+
                     KeyCode::Char('m') => {
-                        self.render_mode = match self.render_mode {
-                            RenderMode::Cast => RenderMode::Fit,
-                            RenderMode::Fit => RenderMode::Cast,
-                        };
+                        self.render_mode = self.render_mode.next();
                         let render_mode = self.render_mode;
+                        self.engine.set_cell_pixels(render_mode.cell_pixels());
                         log_msg("info", &format!("Render mode changed to {render_mode:?}"));
                     }
 
                     KeyCode::Char('f') => self.hint_mode_active = true,
-                    KeyCode::Char('s') => {
-                        if self.demo.is_empty() {
-                            self.auto_scroll = match self.auto_scroll {
-                                AutoScroll::Off => AutoScroll::Linear,
-                                AutoScroll::Linear => AutoScroll::RandomWalk,
-                                AutoScroll::RandomWalk => AutoScroll::Off,
-                                _ => AutoScroll::Off,
-                            };
-                        }
+                    KeyCode::Char('s') if self.demo.is_empty() => {
+                        self.auto_scroll = match self.auto_scroll {
+                            AutoScroll::Off => AutoScroll::Linear,
+                            AutoScroll::Linear => AutoScroll::RandomWalk,
+                            AutoScroll::RandomWalk => AutoScroll::Off,
+                            _ => AutoScroll::Off,
+                        };
+                    }
+
+                    KeyCode::Char('t') => {
+                        self.open_buffer(String::new());
+                        self.open_prompt(Prompt::Url);
                     }
+                    KeyCode::Char('x') => self.close_buffer(),
+                    KeyCode::Char('J') | KeyCode::Tab => self.switch_buffer(1),
+                    KeyCode::Char('K') | KeyCode::BackTab => self.switch_buffer(-1),
+                    KeyCode::Char('>') => self.move_buffer(1),
+                    KeyCode::Char('<') => self.move_buffer(-1),
 
                     KeyCode::Left if self.mode == AppMode::Video => {
                         self.engine.seek(-5.0, term_w as usize, term_h as usize);
@@ -329,12 +609,56 @@ impl App {
                         self.reset_demo_index();
                     }
 
+                    KeyCode::Char('[') if self.mode == AppMode::Video => {
+                        self.step_rate(-1, term_w, term_h);
+                    }
+                    KeyCode::Char(']') if self.mode == AppMode::Video => {
+                        self.step_rate(1, term_w, term_h);
+                    }
+                    KeyCode::Char('=') if self.mode == AppMode::Video => {
+                        self.engine.set_rate(1.0, term_w as usize, term_h as usize);
+                    }
+                    KeyCode::Char('a') if self.mode == AppMode::Video => self.cycle_ab_loop(),
+                    KeyCode::Char(',') if self.mode == AppMode::Video => {
+                        self.jump_demo_entry(false)
+                    }
+                    KeyCode::Char('.') if self.mode == AppMode::Video => self.jump_demo_entry(true),
+                    KeyCode::Char('g') if self.mode == AppMode::Video => {
+                        self.open_prompt(Prompt::GotoTime)
+                    }
+                    KeyCode::Char('D') if self.mode == AppMode::Video => {
+                        self.dither = self.dither.next();
+                        let dither = self.dither;
+                        log_msg("info", &format!("Dithering changed to {dither:?}"));
+                    }
+                    KeyCode::Char('n')
+                        if self.mode == AppMode::Video && self.playlist.skip(true) =>
+                    {
+                        self.play_current_item()
+                    }
+                    KeyCode::Char('N')
+                        if self.mode == AppMode::Video && self.playlist.skip(false) =>
+                    {
+                        self.play_current_item()
+                    }
+                    KeyCode::Char('L') if self.mode == AppMode::Video => {
+                        self.playlist.set_mode(self.playlist.mode().next());
+                        let mode = self.playlist.mode();
+                        log_msg("info", &format!("Playlist mode changed to {mode:?}"));
+                    }
+                    KeyCode::Char('T') if self.mode == AppMode::Video => {
+                        self.theme_index = (self.theme_index + 1) % self.themes.len();
+                        let name = &self.theme().name;
+                        log_msg("info", &format!("Theme changed to {name}"));
+                    }
+
                     KeyCode::Up => {
                         if self.auto_scroll != AutoScroll::Off {
                             self.scroll_speed_multiplier =
                                 (self.scroll_speed_multiplier + 0.25).min(3.0);
                         } else {
-                            self.scroll_y = self.scroll_y.saturating_sub(1);
+                            let buffer = self.buffer_mut();
+                            buffer.scroll_y = buffer.scroll_y.saturating_sub(1);
                         }
                     }
                     KeyCode::Down => {
@@ -347,29 +671,33 @@ impl App {
                     }
 
                     KeyCode::Char('j') => self.scroll_down(term_h),
-                    KeyCode::Char('k') => self.scroll_y = self.scroll_y.saturating_sub(1),
+                    KeyCode::Char('k') => {
+                        let buffer = self.buffer_mut();
+                        buffer.scroll_y = buffer.scroll_y.saturating_sub(1);
+                    }
                     KeyCode::PageDown => self.scroll_down_pg(term_h),
-                    KeyCode::PageUp => self.scroll_y = self.scroll_y.saturating_sub(10),
+                    KeyCode::PageUp => {
+                        let buffer = self.buffer_mut();
+                        buffer.scroll_y = buffer.scroll_y.saturating_sub(10);
+                    }
 
                     KeyCode::Char('h') => {
-                        if self.history_index > 0 {
-                            self.history_index -= 1;
-                            let u = self.history[self.history_index].clone();
+                        let buffer = self.buffer_mut();
+                        if buffer.history_index > 0 {
+                            buffer.history_index -= 1;
+                            let u = buffer.history[buffer.history_index].clone();
                             self.trigger_fetch(u, false, true);
                         }
                     }
                     KeyCode::Char('l') => {
-                        if self.history_index + 1 < self.history.len() {
-                            self.history_index += 1;
-                            let u = self.history[self.history_index].clone();
+                        let buffer = self.buffer_mut();
+                        if buffer.history_index + 1 < buffer.history.len() {
+                            buffer.history_index += 1;
+                            let u = buffer.history[buffer.history_index].clone();
                             self.trigger_fetch(u, false, true);
                         }
                     }
-                    KeyCode::Char('r') => {
-                        if !self.apply_prefetch() {
-                            self.trigger_random_prefetch();
-                        }
-                    }
+                    KeyCode::Char('r') => self.advance_random_walk(),
                     _ => {}
                 }
             }
@@ -377,19 +705,142 @@ impl App {
         false
     }
 
-    fn handle_insert(&mut self, key: KeyCode, modifiers: KeyModifiers) {
+    fn open_prompt(&mut self, prompt: Prompt) {
+        self.previous_mode = self.mode;
+        self.mode = AppMode::Insert;
+        self.prompt = prompt;
+        self.prompt_input.clear();
+        self.prompt_cursor = 0;
+        self.update_completions();
+    }
+
+    fn update_completions(&mut self) {
+        self.completion_selected = 0;
+        self.completions.clear();
+        if self.prompt != Prompt::Url {
+            return;
+        }
+        let query = &self.buffers[self.active_buffer].url_input;
+        self.completions = self
+            .history_db
+            .complete(query, MAX_COMPLETIONS)
+            .into_iter()
+            .filter(|e| e.url != *query)
+            .map(|e| (e.url.clone(), e.title.clone()))
+            .collect();
+    }
+
+    fn accept_completion(&mut self) {
+        if let Some((url, _)) = self.completions.get(self.completion_selected) {
+            let url = url.clone();
+            let buffer = self.buffer_mut();
+            buffer.cursor_pos = url.len();
+            buffer.url_input = url;
+        }
+    }
+
+    fn submit_prompt(&mut self) {
+        self.mode = self.previous_mode;
+        match self.prompt {
+            Prompt::Url => {
+                let u = self.buffer().url_input.clone();
+                self.trigger_fetch(u, false, false);
+            }
+            Prompt::BookmarkTags => {
+                let tags = self
+                    .prompt_input
+                    .split([',', ' '])
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                let buffer = self.buffer();
+                let title = if buffer.page_title.is_empty() {
+                    buffer.title()
+                } else {
+                    buffer.page_title.clone()
+                };
+                let url = buffer.current_url.clone();
+                log_msg("info", &format!("Bookmarked {url}"));
+                self.bookmarks.add(title, url, tags);
+            }
+            Prompt::GotoTime => match parse_timestamp(self.prompt_input.trim()) {
+                Ok(time) => self.seek_video_to(time),
+                Err(e) => log_msg("error", &format!("Go to time: {e}")),
+            },
+        }
+    }
+
+    fn handle_quickmark(&mut self, mark: char, key: char) {
+        if mark == 'M' {
+            let url = self.buffer().current_url.clone();
+            log_msg("info", &format!("Quickmark '{key}' set to {url}"));
+            self.bookmarks.set_quickmark(key, url);
+        } else if let Some(url) = self.bookmarks.quickmark(key) {
+            let u = url.clone();
+            self.trigger_fetch(u, false, false);
+        }
+    }
+
+    fn handle_bookmarks(&mut self, key: KeyCode) {
+        let count = self.bookmarks.bookmarks.len();
         match key {
-            KeyCode::Enter => {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('b') => {
                 self.mode = self.previous_mode;
-                let u = self.url_input.clone();
-                self.trigger_fetch(u, false, false);
             }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.bookmark_selected = (self.bookmark_selected + 1).min(count.saturating_sub(1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.bookmark_selected = self.bookmark_selected.saturating_sub(1);
+            }
+            KeyCode::Char('d') => {
+                self.bookmarks.remove(self.bookmark_selected);
+                self.bookmark_selected = self.bookmark_selected.min(count.saturating_sub(2));
+            }
+            KeyCode::Enter | KeyCode::Char('t') => {
+                if let Some(bookmark) = self.bookmarks.bookmarks.get(self.bookmark_selected) {
+                    let u = bookmark.url.clone();
+                    self.mode = self.previous_mode;
+                    if key == KeyCode::Enter {
+                        self.trigger_fetch(u, false, false);
+                    } else {
+                        self.open_buffer(u);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_insert(&mut self, key: KeyCode, modifiers: KeyModifiers) {
+        let (input, cursor) = match self.prompt {
+            Prompt::Url => {
+                let buffer = &mut self.buffers[self.active_buffer];
+                (&mut buffer.url_input, &mut buffer.cursor_pos)
+            }
+            _ => (&mut self.prompt_input, &mut self.prompt_cursor),
+        };
+
+        match key {
+            KeyCode::Enter => self.submit_prompt(),
             KeyCode::Esc => self.mode = self.previous_mode,
+            KeyCode::Tab => self.accept_completion(),
+            KeyCode::Down | KeyCode::BackTab | KeyCode::Up => {
+                let count = self.completions.len();
+                if count > 0 {
+                    self.completion_selected = if key == KeyCode::Down {
+                        (self.completion_selected + 1) % count
+                    } else {
+                        (self.completion_selected + count - 1) % count
+                    };
+                }
+                return;
+            }
             KeyCode::Backspace => {
                 if modifiers.contains(KeyModifiers::ALT) {
-                    self.delete_word();
+                    delete_word(input, cursor);
                 } else {
-                    delete_prev_grapheme(&mut self.url_input, &mut self.cursor_pos);
+                    delete_prev_grapheme(input, cursor);
                 }
             }
             KeyCode::Char('h')
@@ -398,106 +849,113 @@ impl App {
             {
                 // Ctrl+H is backspace on many terminals
                 if modifiers.contains(KeyModifiers::ALT) {
-                    self.delete_word();
+                    delete_word(input, cursor);
                 } else {
-                    delete_prev_grapheme(&mut self.url_input, &mut self.cursor_pos);
+                    delete_prev_grapheme(input, cursor);
                 }
             }
             KeyCode::Delete => {
-                delete_next_grapheme(&mut self.url_input, &mut self.cursor_pos);
+                delete_next_grapheme(input, cursor);
+            }
+            KeyCode::Char('w') if modifiers.contains(KeyModifiers::CONTROL) => {
+                delete_word(input, cursor)
             }
-            KeyCode::Char('w') if modifiers.contains(KeyModifiers::CONTROL) => self.delete_word(),
             KeyCode::Char('u') if modifiers.contains(KeyModifiers::CONTROL) => {
-                if self.cursor_pos > 0 {
-                    self.url_input.drain(..self.cursor_pos);
+                if *cursor > 0 {
+                    input.drain(..*cursor);
                 }
-                self.cursor_pos = 0;
+                *cursor = 0;
             }
             KeyCode::Char('k') if modifiers.contains(KeyModifiers::CONTROL) => {
-                let cursor = clamp_cursor(&self.url_input, self.cursor_pos);
-                self.url_input.truncate(cursor);
-                self.cursor_pos = cursor;
+                let clamped = clamp_cursor(input, *cursor);
+                input.truncate(clamped);
+                *cursor = clamped;
             }
             KeyCode::Char('a') if modifiers.contains(KeyModifiers::CONTROL) => {
-                self.cursor_pos = 0;
+                *cursor = 0;
             }
             KeyCode::Char('e') if modifiers.contains(KeyModifiers::CONTROL) => {
-                self.cursor_pos = self.url_input.len();
+                *cursor = input.len();
             }
-            KeyCode::Home => self.cursor_pos = 0,
-            KeyCode::End => self.cursor_pos = self.url_input.len(),
+            KeyCode::Home => *cursor = 0,
+            KeyCode::End => *cursor = input.len(),
             KeyCode::Left => {
                 if modifiers.contains(KeyModifiers::CONTROL) {
-                    self.move_word_backward();
+                    move_word_backward(input, cursor);
                 } else {
-                    move_left_grapheme(&self.url_input, &mut self.cursor_pos);
+                    move_left_grapheme(input, cursor);
                 }
             }
             KeyCode::Right => {
                 if modifiers.contains(KeyModifiers::CONTROL) {
-                    self.move_word_forward();
+                    move_word_forward(input, cursor);
                 } else {
-                    move_right_grapheme(&self.url_input, &mut self.cursor_pos);
+                    move_right_grapheme(input, cursor);
                 }
             }
             KeyCode::Char(c) if modifiers.is_empty() || modifiers == KeyModifiers::SHIFT => {
-                insert_grapheme(&mut self.url_input, &mut self.cursor_pos, c);
+                insert_grapheme(input, cursor, c);
             }
             _ => {}
         }
-    }
 
-    fn delete_word(&mut self) {
-        if self.cursor_pos == 0 {
-            return;
+        if self.mode == AppMode::Insert {
+            self.update_completions();
         }
-        delete_word(&mut self.url_input, &mut self.cursor_pos);
-    }
-
-    fn move_word_backward(&mut self) {
-        move_word_backward(&self.url_input, &mut self.cursor_pos);
-    }
-
-    fn move_word_forward(&mut self) {
-        move_word_forward(&self.url_input, &mut self.cursor_pos);
     }
 
     pub fn scroll_down(&mut self, term_h: u16) {
-        self.scroll_y = self.scroll_y.saturating_add(1);
+        let buffer = self.buffer_mut();
+        buffer.scroll_y = buffer.scroll_y.saturating_add(1);
         self.check_random_walk_trigger(term_h);
     }
 
     fn scroll_down_pg(&mut self, term_h: u16) {
-        self.scroll_y = self.scroll_y.saturating_add(10);
+        let buffer = self.buffer_mut();
+        buffer.scroll_y = buffer.scroll_y.saturating_add(10);
         self.check_random_walk_trigger(term_h);
     }
 
     fn check_random_walk_trigger(&mut self, term_h: u16) {
         if self.auto_scroll == AutoScroll::RandomWalk {
-            let lines = self.page_text.lines().count();
-            if (self.scroll_y as usize) + (term_h as usize) >= lines.saturating_sub(2) {
-                if !self.apply_prefetch() {
-                    self.trigger_random_prefetch();
-                }
+            let buffer = self.buffer();
+            let lines = buffer.page_text.lines().count();
+            if (buffer.scroll_y as usize) + (term_h as usize) >= lines.saturating_sub(2) {
+                self.advance_random_walk();
             }
         }
     }
 
+    fn fetch_images(&self, buffer_id: usize, page_url: &str, images: Vec<ImageRef>) {
+        for (index, image) in images.into_iter().enumerate() {
+            self.web.fetch_image(buffer_id, page_url, index, image);
+        }
+    }
+
+    fn advance_random_walk(&mut self) {
+        if !self.apply_prefetch() {
+            self.trigger_random_prefetch();
+        }
+    }
+
     pub fn stop_video(&mut self) {
         self.mode = AppMode::Normal;
         self.engine.stop();
-        self.demo_index = 0;
+        self.demo.begin();
         self.last_prefetch_index = None;
         self.auto_scroll = AutoScroll::Off;
         self.autoplay = false;
+        self.loop_a = None;
+        self.loop_b = None;
     }
 
     fn trigger_random_prefetch(&mut self) {
-        let current_host = Url::parse(&self.current_url)
+        let buffer = self.buffer();
+        let current_host = Url::parse(&buffer.current_url)
             .ok()
             .and_then(|u| u.host_str().map(|s| s.to_string()));
 
-        let filtered_links: Vec<&String> = self
+        let filtered_links: Vec<&String> = buffer
             .valid_links
             .iter()
             .filter(|link| {
@@ -514,31 +972,45 @@ impl App {
             .collect();
 
         if let Some(link) = filtered_links.choose(&mut rand::rng()) {
-            self.trigger_fetch((*link).clone(), true, false);
-        } else if let Some(link) = self.valid_links.choose(&mut rand::rng()) {
-            self.trigger_fetch(link.clone(), true, false);
+            let link = (*link).clone();
+            self.trigger_fetch(link, true, false);
+        } else if let Some(link) = buffer.valid_links.choose(&mut rand::rng()) {
+            let link = link.clone();
+            self.trigger_fetch(link, true, false);
         }
     }
 
     fn apply_prefetch(&mut self) -> bool {
+        let buffer = self.buffer_mut();
         if let Some(BgEvent::PrefetchReady {
             url,
+            title,
             text,
             dense_text,
             link_map,
             links,
-        }) = self.prefetch_data.take()
+            images,
+            ..
+        }) = buffer.prefetch_data.take()
         {
-            self.current_url = url.clone();
-            self.url_input = url;
-            self.cursor_pos = self.url_input.len();
-            self.page_text = Arc::new(text);
-            self.dense_text = Arc::new(dense_text);
-            self.link_map = Arc::new(link_map);
-            self.valid_links = Arc::new(links);
-            self.scroll_y = 0;
-            self.history.push(self.current_url.clone());
-            self.history_index = self.history.len() - 1;
+            buffer.show_page(
+                url.clone(),
+                title,
+                (
+                    Arc::new(text),
+                    Arc::new(dense_text),
+                    Arc::new(link_map),
+                    Arc::new(links),
+                ),
+            );
+            buffer.push_history(url);
+            let (id, url, title) = (
+                buffer.id,
+                buffer.current_url.clone(),
+                buffer.page_title.clone(),
+            );
+            self.history_db.record_visit(&url, &title);
+            self.fetch_images(id, &url, images);
 
             self.trigger_random_prefetch();
             return true;
@@ -546,36 +1018,94 @@ impl App {
         false
     }
 
-    fn reset_demo_index(&mut self) {
-        if self.demo.is_empty() {
+    fn step_rate(&mut self, delta: isize, term_w: u16, term_h: u16) {
+        let current = self.engine.clock.rate();
+        let index = PLAYBACK_RATES
+            .iter()
+            .position(|&r| r >= current)
+            .unwrap_or(PLAYBACK_RATES.len() - 1);
+        let next = index
+            .saturating_add_signed(delta)
+            .min(PLAYBACK_RATES.len() - 1);
+        self.engine
+            .set_rate(PLAYBACK_RATES[next], term_w as usize, term_h as usize);
+    }
+
+    /// Seeks to `time` and shows the demo page that is due there.
+    fn seek_video_to(&mut self, time: f64) {
+        let (term_w, term_h) = self.engine.term_size;
+        self.engine.seek_to(time, term_w, term_h);
+        if let Some(index) = self.reset_demo_index() {
+            self.apply_demo_page(index);
+        }
+    }
+
+    /// `,`/`.`: previous or next `ScriptEntry`. Going back from less than a second into an
+    /// entry skips to the one before it, like a media player's previous-chapter key.
+    fn jump_demo_entry(&mut self, forward: bool) {
+        let now = self.engine.clock.position();
+        let target = if forward {
+            self.demo
+                .entries()
+                .iter()
+                .find(|e| e.timestamp > now + 0.05)
+        } else {
+            let mut entries = self.demo.entries().iter().rev();
+            entries.find(|e| e.timestamp < now - 1.0)
+        };
+        match target.map(|e| e.timestamp) {
+            Some(time) => self.seek_video_to(time),
+            None if !forward => self.seek_video_to(0.0),
+            None => {}
+        }
+    }
+
+    /// `a` sets A, then B, then clears the loop.
+    fn cycle_ab_loop(&mut self) {
+        let now = self.engine.clock.position();
+        match (self.loop_a, self.loop_b) {
+            (Some(a), None) if now > a => self.loop_b = Some(now),
+            (None, _) | (Some(_), None) => self.loop_a = Some(now),
+            (Some(_), Some(_)) => {
+                self.loop_a = None;
+                self.loop_b = None;
+            }
+        }
+        log_msg(
+            "info",
+            &format!("A-B loop: {:?} -> {:?}", self.loop_a, self.loop_b),
+        );
+    }
+
+    pub fn check_ab_loop(&mut self) {
+        if self.mode != AppMode::Video {
             return;
         }
+        if let (Some(a), Some(b)) = (self.loop_a, self.loop_b)
+            && self.engine.clock.position() >= b
+        {
+            self.seek_video_to(a);
+        }
+    }
 
-        let current_time = self.engine.seek_time;
-        self.demo_index = self
-            .demo
-            .iter()
-            .position(|e| e.timestamp > current_time)
-            .unwrap_or(self.demo.len());
+    /// Catches the demo up with a seek, returning the entry whose page is due now.
+    fn reset_demo_index(&mut self) -> Option<usize> {
         self.last_prefetch_index = None;
+        self.demo.seek(self.engine.clock.position())
     }
 
     fn apply_demo_page(&mut self, index: usize) {
-        if index >= self.demo.len() {
+        let Some(entry) = self.demo.entries().get(index) else {
             return;
-        }
-
-        let url = &self.demo[index].url;
+        };
+        let url = &entry.url;
 
-        if let Some((text, dense_text, link_map, links)) = self.demo_cache.get(url) {
-            self.current_url = url.clone();
-            self.url_input = url.clone();
-            self.cursor_pos = self.url_input.len();
-            self.page_text = Arc::clone(text);
-            self.dense_text = Arc::clone(dense_text);
-            self.link_map = Arc::clone(link_map);
-            self.valid_links = Arc::clone(links);
-            self.scroll_y = 0;
+        if let Some((page, images)) = self.demo_cache.get(url) {
+            let (page, images, url) = (page.clone(), images.clone(), url.clone());
+            let buffer = self.buffer_mut();
+            buffer.show_page(url.clone(), String::new(), page);
+            let buffer_id = buffer.id;
+            self.fetch_images(buffer_id, &url, images);
         } else {
             log_msg(
                 "warn",
@@ -584,24 +1114,144 @@ impl App {
         }
     }
 
+    /// Starts the current video's demo script from its first page.
+    fn begin_demo(&mut self) {
+        self.last_prefetch_index = None;
+        let Some(first) = self.demo.begin() else {
+            if self.auto_scroll == AutoScroll::Demo {
+                self.auto_scroll = AutoScroll::Off;
+            }
+            return;
+        };
+        self.auto_scroll = AutoScroll::Demo;
+        log_msg(
+            "info",
+            &format!(
+                "Demo: {} entries, {} cached",
+                self.demo.len(),
+                self.demo_cache.len()
+            ),
+        );
+        self.apply_demo_page(first);
+    }
+
+    fn is_demo_url(&self, url: &str) -> bool {
+        let items = self.playlist.items();
+        items
+            .iter()
+            .any(|item| item.demo.iter().any(|e| e.url == url))
+    }
+
+    /// Loads and plays the playlist's current video with its demo.
+    fn play_current_item(&mut self) {
+        self.load_current_item();
+        let (term_w, term_h) = self.engine.term_size;
+        self.engine.start(term_w, term_h, 0.0);
+        self.begin_demo();
+    }
+
+    /// Switches the engine and demo to the playlist's current video without playing it.
+    fn load_current_item(&mut self) {
+        let item = self.playlist.current();
+        log_msg(
+            "info",
+            &format!(
+                "Playlist: {}/{} {}",
+                self.playlist.current_index() + 1,
+                self.playlist.len(),
+                item.video
+            ),
+        );
+        self.demo = DemoTimeline::new(item.demo.clone());
+        let video = item.video.clone();
+        self.engine.load(&video);
+        self.loop_a = None;
+        self.loop_b = None;
+    }
+
     pub fn check_demo_transitions(&mut self) {
         if self.demo.is_empty() || self.mode != AppMode::Video {
             return;
         }
 
-        let current_time = if self.engine.is_paused {
-            self.engine.seek_time
-        } else {
-            self.engine.seek_time + self.engine.start_instant.elapsed().as_secs_f64()
-        };
+        if let Some(index) = self.demo.advance(self.engine.clock.position()) {
+            self.apply_demo_page(index);
+        }
+    }
+}
 
-        if self.demo_index < self.demo.len() {
-            let entry_timestamp = self.demo[self.demo_index].timestamp;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioConfig, AudioKind};
+    use crate::playlist::{PlaylistItem, PlaylistMode};
+    use crate::video::DEFAULT_CELL_ASPECT;
 
-            if current_time >= entry_timestamp {
-                self.apply_demo_page(self.demo_index);
-                self.demo_index += 1;
-            }
+    fn app(videos: usize, mode: PlaylistMode) -> App {
+        let items = (0..videos)
+            .map(|_| PlaylistItem {
+                video: "synthetic:flash:0.1".into(),
+                demo: vec![ScriptEntry {
+                    timestamp: 0.0,
+                    url: "http://127.0.0.1:9/demo".into(),
+                }],
+            })
+            .collect();
+        let video = VideoOptions {
+            audio: AudioConfig {
+                kind: AudioKind::Null,
+                command: None,
+            },
+            cell_aspect: DEFAULT_CELL_ASPECT,
+            color: None,
+        };
+        let mask = MaskOptions {
+            dither: Dither::None,
+            themes: Theme::builtin(),
+            theme_index: 0,
+        };
+        let playlist = Playlist::new(items, mode);
+        let mut app = App::new(playlist, String::new(), "test".into(), None, video, mask);
+        // Keep the user's history out of it
+        app.history_db = HistoryDb::default();
+        app.mode = AppMode::Video;
+        app.play_current_item();
+        app
+    }
+
+    /// Handles events until the playing video has ended and the app has reacted.
+    fn finish_video(app: &mut App) {
+        let session = app.engine.session_id;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.engine.session_id == session && app.mode == AppMode::Video {
+            assert!(Instant::now() < deadline, "video never ended");
+            app.handle_events();
+            std::thread::sleep(Duration::from_millis(5));
         }
     }
+
+    #[test]
+    fn ended_videos_advance_the_playlist_even_with_autoplay() {
+        let mut looping = app(2, PlaylistMode::LoopAll);
+        looping.autoplay = true;
+        finish_video(&mut looping);
+        assert_eq!(looping.playlist.current_index(), 1);
+        finish_video(&mut looping);
+        assert_eq!(looping.playlist.current_index(), 0);
+        assert_eq!(looping.mode, AppMode::Video);
+
+        // Autoplay starts a finished playlist over instead of stopping
+        let mut once = app(2, PlaylistMode::Once);
+        once.autoplay = true;
+        finish_video(&mut once);
+        finish_video(&mut once);
+        assert_eq!(once.playlist.current_index(), 0);
+        assert_eq!(once.mode, AppMode::Video);
+
+        once.autoplay = false;
+        finish_video(&mut once);
+        finish_video(&mut once);
+        assert_eq!(once.mode, AppMode::Normal);
+        assert_eq!(once.playlist.current_index(), 0);
+    }
 }
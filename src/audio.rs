@@ -0,0 +1,314 @@
+use crate::utils::log_msg;
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use std::io::{self, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// Plays the soundtrack alongside the frames. Positions are in video seconds.
+pub trait AudioBackend: Send {
+    /// Starts playing at `position`, replacing whatever was playing.
+    fn start(&mut self, position: f64);
+    fn pause(&mut self);
+    fn resume(&mut self, position: f64);
+    fn stop(&mut self);
+    /// Playback speed for the next `start`; players that can retime live apply it at once.
+    fn set_rate(&mut self, rate: f64);
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum AudioKind {
+    Ffplay,
+    Mpv,
+    /// The `--audio-command` template
+    Command,
+    Null,
+}
+
+#[derive(Clone)]
+pub struct AudioConfig {
+    pub kind: AudioKind,
+    pub command: Option<String>,
+}
+
+pub fn open_backend(config: &AudioConfig, path: &str) -> Box<dyn AudioBackend> {
+    match config.kind {
+        AudioKind::Ffplay => Box::new(ProcessAudio::ffplay(path)),
+        AudioKind::Mpv => Box::new(MpvAudio::new(path)),
+        AudioKind::Command => match &config.command {
+            Some(template) => Box::new(ProcessAudio::template(template, path)),
+            None => {
+                log_msg("error", "Audio: --audio command needs --audio-command");
+                Box::new(NullAudio)
+            }
+        },
+        AudioKind::Null => Box::new(NullAudio),
+    }
+}
+
+/// Silent sink for `--no-audio`, CI and sources without a soundtrack.
+pub struct NullAudio;
+
+impl AudioBackend for NullAudio {
+    fn start(&mut self, _position: f64) {}
+    fn pause(&mut self) {}
+    fn resume(&mut self, _position: f64) {}
+    fn stop(&mut self) {}
+    fn set_rate(&mut self, _rate: f64) {}
+}
+
+/// A player process that can only be started at a position. Pausing sends SIGSTOP and
+/// resuming SIGCONT; anything that fails falls back to a fresh process.
+pub struct ProcessAudio {
+    command: Box<dyn Fn(f64, f64) -> Command + Send>,
+    child: Option<Child>,
+    rate: f64,
+}
+
+impl ProcessAudio {
+    pub fn ffplay(path: &str) -> Self {
+        let path = path.to_string();
+        Self::new(move |position, rate| {
+            let mut cmd = Command::new("ffplay");
+            if rate != 1.0 {
+                cmd.args(["-af", &format!("atempo={rate}")]);
+            }
+            cmd.args([
+                "-ss",
+                &format!("{position:.2}"),
+                "-nodisp",
+                "-autoexit",
+                "-hide_banner",
+                "-loglevel",
+                "panic",
+                "-fflags",
+                "nobuffer",
+                "-flags",
+                "low_delay",
+                "-analyzeduration",
+                "0",
+                "-probesize",
+                "32",
+                &path,
+            ]);
+            cmd
+        })
+    }
+
+    /// Runs `template` through `sh`, with `{path}` replaced by the quoted video path,
+    /// `{seek}` by the start position in seconds and `{rate}` by the playback speed.
+    pub fn template(template: &str, path: &str) -> Self {
+        let template = template.replace("{path}", &shell_quote(path));
+        Self::new(move |position, rate| {
+            let script = template
+                .replace("{seek}", &format!("{position:.2}"))
+                .replace("{rate}", &rate.to_string());
+            let mut cmd = Command::new("sh");
+            // exec so the signals reach the player rather than the shell
+            cmd.arg("-c").arg(format!("exec {script}"));
+            cmd
+        })
+    }
+
+    fn new(command: impl Fn(f64, f64) -> Command + Send + 'static) -> Self {
+        Self {
+            command: Box::new(command),
+            child: None,
+            rate: 1.0,
+        }
+    }
+
+    fn signal(&self, signal: &str) -> bool {
+        let Some(child) = &self.child else {
+            return false;
+        };
+        Command::new("kill")
+            .arg(signal)
+            .arg(child.id().to_string())
+            .output()
+            .is_ok_and(|o| o.status.success())
+    }
+}
+
+impl AudioBackend for ProcessAudio {
+    fn start(&mut self, position: f64) {
+        self.stop();
+        self.child = (self.command)(position, self.rate)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .inspect_err(|e| log_msg("error", &format!("Audio: failed to spawn player: {e}")))
+            .ok();
+    }
+
+    fn pause(&mut self) {
+        if !self.signal("-STOP") {
+            self.stop();
+        }
+    }
+
+    fn resume(&mut self, position: f64) {
+        if !self.signal("-CONT") {
+            self.start(position);
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let pid = child.id();
+            let _ = child.kill();
+            let _ = child.wait();
+            // Ensure it's really dead
+            let _ = Command::new("kill").arg("-9").arg(pid.to_string()).output();
+        }
+    }
+
+    fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+}
+
+impl Drop for ProcessAudio {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// mpv kept running between seeks and driven over its JSON IPC socket, so pause and seek
+/// are exact instead of a process restart.
+pub struct MpvAudio {
+    path: String,
+    socket: PathBuf,
+    child: Option<Child>,
+    rate: f64,
+}
+
+impl MpvAudio {
+    pub fn new(path: &str) -> Self {
+        let socket =
+            std::env::temp_dir().join(format!("bad-browser-mpv-{}.sock", std::process::id()));
+        Self {
+            path: path.to_string(),
+            socket,
+            child: None,
+            rate: 1.0,
+        }
+    }
+
+    fn is_running(&mut self) -> bool {
+        self.child
+            .as_mut()
+            .is_some_and(|c| matches!(c.try_wait(), Ok(None)))
+    }
+
+    /// Sends one command on a fresh connection, waiting briefly for a just-spawned mpv to
+    /// create its socket.
+    fn command(&self, args: Value) -> io::Result<()> {
+        let mut last_err = None;
+        for _ in 0..25 {
+            match UnixStream::connect(&self.socket) {
+                Ok(mut stream) => {
+                    return writeln!(stream, "{}", json!({ "command": args }));
+                }
+                Err(e) => {
+                    last_err = Some(e);
+                    thread::sleep(Duration::from_millis(20));
+                }
+            }
+        }
+        Err(last_err.unwrap())
+    }
+
+    fn spawn(&mut self, position: f64) {
+        self.stop();
+        self.child = Command::new("mpv")
+            .args([
+                "--no-video",
+                "--no-terminal",
+                "--idle=no",
+                &format!("--input-ipc-server={}", self.socket.display()),
+                &format!("--start={position:.3}"),
+                &format!("--speed={}", self.rate),
+                &self.path,
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .inspect_err(|e| log_msg("error", &format!("Audio: failed to spawn mpv: {e}")))
+            .ok();
+    }
+}
+
+impl AudioBackend for MpvAudio {
+    fn start(&mut self, position: f64) {
+        let reused = self.is_running()
+            && self
+                .command(json!(["seek", position, "absolute", "exact"]))
+                .is_ok()
+            && self
+                .command(json!(["set_property", "pause", false]))
+                .is_ok();
+        if !reused {
+            self.spawn(position);
+        }
+    }
+
+    fn pause(&mut self) {
+        if let Err(e) = self.command(json!(["set_property", "pause", true])) {
+            log_msg("error", &format!("Audio: mpv pause failed: {e}"));
+        }
+    }
+
+    fn resume(&mut self, position: f64) {
+        if !self.is_running()
+            || self
+                .command(json!(["set_property", "pause", false]))
+                .is_err()
+        {
+            self.spawn(position);
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let _ = std::fs::remove_file(&self.socket);
+    }
+
+    fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+        if self.is_running() {
+            let _ = self.command(json!(["set_property", "speed", rate]));
+        }
+    }
+}
+
+impl Drop for MpvAudio {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_quotes_path_and_fills_seek() {
+        let audio = ProcessAudio::template("paplay --seek {seek} {path}", "it's.mp4");
+        let cmd = (audio.command)(12.5, 1.0);
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(args, ["-c", r"exec paplay --seek 12.50 'it'\''s.mp4'"]);
+    }
+}
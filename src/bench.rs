@@ -0,0 +1,98 @@
+use crate::frame::FrameBuffer;
+use crate::source::SourceSpec;
+use crate::theme::Theme;
+use crate::types::RenderMode;
+use crate::ui::{MaskFrame, VideoMask};
+use anyhow::{Result, ensure};
+use clap::ValueEnum;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::widgets::Widget;
+use std::time::{Duration, Instant};
+
+/// Page text under the mask, so the benchmark needs no network.
+const TEXT: &str = "Bad Apple!! Touhou Project. ";
+
+/// Draws `frames` frames of `video_path` in `render_mode` into a `width x height` buffer,
+/// publishing each through a `FrameBuffer` as playback does. Frames are decoded up front
+/// and nothing is written to a terminal, so only the handoff and the widget are timed.
+fn measure(
+    video_path: &str,
+    width: u16,
+    height: u16,
+    frames: usize,
+    render_mode: RenderMode,
+) -> Result<Duration> {
+    let (nx, ny) = render_mode.cell_pixels();
+    let (w, h) = (width as usize * nx, height as usize * ny);
+    let mut source = SourceSpec::parse(video_path)?.open(w, h, 0.0, 1.0, false)?;
+    let size = source.size();
+    // Prerendered frames keep their own size at one pixel per cell
+    let cell_pixels = if size == (w, h) { (nx, ny) } else { (1, 1) };
+
+    let mut pixels = vec![0u8; size.0 * size.1 * source.channels()];
+    let mut decoded = Vec::new();
+    while decoded.len() < frames && source.next_frame(&mut pixels)?.is_some() {
+        decoded.push(pixels.clone());
+    }
+    ensure!(!decoded.is_empty(), "{video_path} has no frames");
+
+    let text: Vec<char> = TEXT.chars().collect();
+    let theme = Theme::classic();
+    let area = Rect::new(0, 0, width, height);
+    let mut buffer = Buffer::empty(area);
+    let output = FrameBuffer::default();
+
+    let start = Instant::now();
+    for pixels in decoded.iter().cycle().take(frames) {
+        output.publish(pixels, size.0, size.1, cell_pixels);
+        let latest = output.latest();
+        let frame = MaskFrame {
+            pixels: &latest.pixels,
+            width: latest.width,
+            height: latest.height,
+            cell_pixels: latest.cell_pixels,
+            color: None,
+        };
+        buffer.reset();
+        VideoMask {
+            frame: &frame,
+            dense_text: &text,
+            scroll_y: 0,
+            render_mode,
+            theme: &theme,
+        }
+        .render(area, &mut buffer);
+    }
+    Ok(start.elapsed())
+}
+
+/// Prints the render throughput of every render mode.
+pub fn run(video_path: &str, width: u16, height: u16, frames: usize) -> Result<()> {
+    ensure!(frames > 0, "--bench needs at least one frame");
+    println!("{width}x{height} cells, {frames} frames of {video_path}");
+    for &mode in RenderMode::value_variants() {
+        let elapsed = measure(video_path, width, height, frames, mode)?.as_secs_f64();
+        let name = mode.to_possible_value().unwrap();
+        println!(
+            "{:<12} {:>10.1} frames/s {:>8.3} ms/frame",
+            name.get_name(),
+            frames as f64 / elapsed,
+            elapsed * 1000.0 / frames as f64
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_every_mode_on_a_synthetic_source() {
+        // Shorter than the frame count, so the decoded frames are cycled
+        for &mode in RenderMode::value_variants() {
+            assert!(measure("synthetic:circle:0.1", 20, 10, 8, mode).is_ok());
+        }
+    }
+}
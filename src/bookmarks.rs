@@ -0,0 +1,202 @@
+use crate::utils::{data_dir, escape_html, log_msg, unescape_html};
+use anyhow::Result;
+use chrono::Utc;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Unix timestamp (seconds) of when the bookmark was added.
+    pub added: i64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct BookmarkStore {
+    pub bookmarks: Vec<Bookmark>,
+    /// Vim-style quickmarks: a single letter mapped to a URL.
+    #[serde(default)]
+    pub quickmarks: BTreeMap<char, String>,
+
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl BookmarkStore {
+    pub fn load() -> Self {
+        let path = data_dir().join(BOOKMARKS_FILE);
+        let mut store = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log_msg(
+                    "error",
+                    &format!("Bookmarks: failed to parse {path:?}: {e}"),
+                );
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        store.path = Some(path);
+        store
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self.save() {
+            log_msg("error", &format!("Bookmarks: failed to save: {e}"));
+        }
+    }
+
+    /// Adds a bookmark, replacing any existing entry for the same URL.
+    pub fn add(&mut self, title: String, url: String, tags: Vec<String>) {
+        self.bookmarks.retain(|b| b.url != url);
+        self.bookmarks.push(Bookmark {
+            title,
+            url,
+            tags,
+            added: Utc::now().timestamp(),
+        });
+        self.persist();
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.bookmarks.len() {
+            self.bookmarks.remove(index);
+            self.persist();
+        }
+    }
+
+    pub fn set_quickmark(&mut self, key: char, url: String) {
+        self.quickmarks.insert(key, url);
+        self.persist();
+    }
+
+    pub fn quickmark(&self, key: char) -> Option<&String> {
+        self.quickmarks.get(&key)
+    }
+
+    /// Merges bookmarks from a Netscape bookmark file, returning how many were added.
+    pub fn import_netscape(&mut self, html: &str) -> usize {
+        let imported = parse_netscape(html);
+        let count = imported.len();
+        for bookmark in imported {
+            self.bookmarks.retain(|b| b.url != bookmark.url);
+            self.bookmarks.push(bookmark);
+        }
+        self.persist();
+        count
+    }
+
+    pub fn export_netscape(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+             <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+             <TITLE>Bookmarks</TITLE>\n\
+             <H1>Bookmarks</H1>\n\
+             <DL><p>\n",
+        );
+        for b in &self.bookmarks {
+            let tags = if b.tags.is_empty() {
+                String::new()
+            } else {
+                format!(" TAGS=\"{}\"", escape_html(&b.tags.join(",")))
+            };
+            out.push_str(&format!(
+                "    <DT><A HREF=\"{url}\" ADD_DATE=\"{added}\"{tags}>{title}</A>\n",
+                url = escape_html(&b.url),
+                added = b.added,
+                title = escape_html(&b.title),
+            ));
+        }
+        out.push_str("</DL><p>\n");
+        out
+    }
+}
+
+fn parse_netscape(html: &str) -> Vec<Bookmark> {
+    let anchor_regex = Regex::new(r"(?is)<a\s([^>]*)>(.*?)</a>").unwrap();
+    let attr_regex = Regex::new(r#"(?i)([a-z_]+)\s*=\s*"([^"]*)""#).unwrap();
+
+    anchor_regex
+        .captures_iter(html)
+        .filter_map(|caps| {
+            let mut url = None;
+            let mut added = 0;
+            let mut tags = Vec::new();
+            for attr in attr_regex.captures_iter(&caps[1]) {
+                let value = unescape_html(&attr[2]);
+                match attr[1].to_ascii_uppercase().as_str() {
+                    "HREF" => url = Some(value),
+                    "ADD_DATE" => added = value.parse().unwrap_or(0),
+                    "TAGS" => {
+                        tags = value
+                            .split(',')
+                            .map(|t| t.trim().to_string())
+                            .filter(|t| !t.is_empty())
+                            .collect();
+                    }
+                    _ => {}
+                }
+            }
+            Some(Bookmark {
+                title: unescape_html(caps[2].trim()),
+                url: url?,
+                tags,
+                added,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn netscape_round_trip_keeps_tags_and_dates() {
+        let mut store = BookmarkStore::default();
+        store.bookmarks.push(Bookmark {
+            title: "Bad Apple!! & friends".to_string(),
+            url: "https://en.touhouwiki.net/wiki/Bad_Apple!!".to_string(),
+            tags: vec!["touhou".to_string(), "music".to_string()],
+            added: 1_700_000_000,
+        });
+
+        let html = store.export_netscape();
+        assert_eq!(parse_netscape(&html), store.bookmarks);
+    }
+
+    #[test]
+    fn parses_browser_exports() {
+        let html = r#"<DL><p>
+            <DT><H3>Folder</H3>
+            <DL><p>
+                <DT><a href="https://example.com/" add_date="42" icon="data:x">Example</a>
+                <DT><A>No link</A>
+            </DL><p>
+        </DL>"#;
+
+        let parsed = parse_netscape(html);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].url, "https://example.com/");
+        assert_eq!(parsed[0].title, "Example");
+        assert_eq!(parsed[0].added, 42);
+    }
+}
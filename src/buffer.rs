@@ -0,0 +1,89 @@
+use crate::types::{BgEvent, CachedPage};
+use crate::utils::decode_url;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub struct Buffer {
+    pub id: usize,
+    pub is_loading: bool,
+    pub prefetch_data: Option<BgEvent>,
+
+    pub current_url: String,
+    pub page_title: String,
+    pub url_input: String,
+    pub cursor_pos: usize,
+    pub page_text: Arc<String>,
+    pub dense_text: Arc<Vec<char>>,
+
+    pub link_map: Arc<HashMap<String, String>>,
+    pub valid_links: Arc<Vec<String>>,
+
+    pub history: Vec<String>,
+    pub history_index: usize,
+    pub scroll_y: u16,
+    /// Scroll position to restore once the next page load lands (session restore).
+    pub pending_scroll: Option<u16>,
+}
+
+impl Buffer {
+    pub fn new(id: usize, url: String) -> Self {
+        Self {
+            id,
+            is_loading: false,
+            prefetch_data: None,
+            current_url: url.clone(),
+            page_title: String::new(),
+            cursor_pos: url.len(),
+            url_input: url,
+            page_text: Arc::new(String::new()),
+            dense_text: Arc::new(Vec::new()),
+            link_map: Arc::new(HashMap::new()),
+            valid_links: Arc::new(Vec::new()),
+            history: vec![],
+            history_index: 0,
+            scroll_y: 0,
+            pending_scroll: None,
+        }
+    }
+
+    /// Replaces the displayed page without touching the history stack.
+    pub fn show_page(&mut self, url: String, title: String, page: CachedPage) {
+        let (text, dense_text, link_map, links) = page;
+        self.current_url = url.clone();
+        self.page_title = title;
+        self.url_input = url;
+        self.cursor_pos = self.url_input.len();
+        self.page_text = text;
+        self.dense_text = dense_text;
+        self.link_map = link_map;
+        self.valid_links = links;
+        self.scroll_y = 0;
+    }
+
+    pub fn push_history(&mut self, url: String) {
+        if self.history.last() == Some(&url) {
+            return;
+        }
+        self.history.truncate(self.history_index + 1);
+        self.history.push(url);
+        self.history_index = self.history.len() - 1;
+    }
+
+    /// Short label for the tab line: the last path segment, or the host for bare domains.
+    pub fn title(&self) -> String {
+        let Ok(url) = Url::parse(&self.current_url) else {
+            return decode_url(&self.current_url);
+        };
+
+        let segment = url
+            .path_segments()
+            .and_then(|mut s| s.rfind(|seg| !seg.is_empty()))
+            .map(decode_url);
+
+        match segment {
+            Some(seg) => seg.replace('_', " "),
+            None => url.host_str().unwrap_or_default().to_string(),
+        }
+    }
+}
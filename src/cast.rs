@@ -0,0 +1,100 @@
+use crate::headless::{HeadlessOptions, buffer_to_ansi, render_frames};
+use anyhow::Result;
+use chrono::Utc;
+use ratatui::buffer::Buffer;
+use serde_json::json;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Hide the cursor and clear the screen before the first frame.
+const CAST_PREAMBLE: &str = "\x1b[?25l\x1b[2J";
+
+/// Streams frames as an asciicast v2 recording: a JSON header line, then one
+/// `[time, "o", data]` event per frame.
+pub struct CastWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> CastWriter<W> {
+    pub fn new(mut out: W, width: u16, height: u16, title: &str) -> Result<Self> {
+        let header = json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": Utc::now().timestamp(),
+            "title": title,
+            "env": { "TERM": "xterm-256color" },
+        });
+        writeln!(out, "{header}")?;
+        Ok(Self { out })
+    }
+
+    fn event(&mut self, time: f64, data: &str) -> Result<()> {
+        writeln!(self.out, "{}", json!([time, "o", data]))?;
+        Ok(())
+    }
+
+    /// Repaints the whole screen from the top-left corner. The final row has no line break so
+    /// the player never scrolls.
+    pub fn frame(&mut self, time: f64, buffer: &Buffer) -> Result<()> {
+        let ansi = buffer_to_ansi(buffer);
+        let data = format!("\x1b[H{}", ansi.strip_suffix("\r\n").unwrap_or(&ansi));
+        self.event(time, &data)
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Renders the performance offscreen and records it to `path`, timed by the video clock.
+pub fn export(opts: &HeadlessOptions, path: &Path) -> Result<()> {
+    let title = format!("bad-browser: {}", opts.video_path);
+    let mut cast = CastWriter::new(
+        BufWriter::new(File::create(path)?),
+        opts.width,
+        opts.height,
+        &title,
+    )?;
+    cast.event(0.0, CAST_PREAMBLE)?;
+
+    let count = render_frames(opts, |time, buffer| cast.frame(time, buffer))?;
+    cast.finish()?;
+    println!("Recorded {count} frames to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+
+    #[test]
+    fn writes_header_and_frame_events() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 3, 2));
+        buffer.set_string(0, 0, "Bad", ratatui::style::Style::default());
+
+        let mut out = Vec::new();
+        let mut cast = CastWriter::new(&mut out, 3, 2, "test").unwrap();
+        cast.frame(0.5, &buffer).unwrap();
+        cast.finish().unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let header: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(header["version"], 2);
+        assert_eq!(
+            (header["width"].as_u64(), header["height"].as_u64()),
+            (Some(3), Some(2))
+        );
+
+        let event: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(event[0], 0.5);
+        assert_eq!(event[1], "o");
+        assert_eq!(event[2], "\x1b[H\x1b[0mBad\x1b[0m\r\n\x1b[0m   \x1b[0m");
+    }
+}
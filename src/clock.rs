@@ -0,0 +1,142 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+/// Frame presentation counters kept next to the clock for the debug overlay.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncStats {
+    /// Frames read from the source, shown or not
+    pub decoded: u64,
+    pub presented: u64,
+    pub dropped: u64,
+    pub repeated: u64,
+    /// Clock position minus the PTS of the last presented frame; positive means video lags
+    pub drift: f64,
+}
+
+struct ClockState {
+    /// Position at `anchor`
+    base: f64,
+    anchor: Instant,
+    paused: bool,
+    rate: f64,
+    stats: SyncStats,
+}
+
+/// The single source of truth for the playback position. Cloning shares the clock, so the
+/// UI, the demo timeline and the frame reader thread all see the same time.
+#[derive(Clone)]
+pub struct PlaybackClock {
+    state: Arc<Mutex<ClockState>>,
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ClockState {
+                base: 0.0,
+                anchor: Instant::now(),
+                paused: false,
+                rate: 1.0,
+                stats: SyncStats::default(),
+            })),
+        }
+    }
+}
+
+impl PlaybackClock {
+    fn lock(&self) -> MutexGuard<'_, ClockState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Current position in video seconds.
+    pub fn position(&self) -> f64 {
+        let s = self.lock();
+        if s.paused {
+            s.base
+        } else {
+            s.base + s.anchor.elapsed().as_secs_f64() * s.rate
+        }
+    }
+
+    /// Jumps to `seconds` and resets the sync counters, keeping the paused state.
+    pub fn set_position(&self, seconds: f64) {
+        let mut s = self.lock();
+        s.base = seconds;
+        s.anchor = Instant::now();
+        s.stats = SyncStats::default();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.lock().paused
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        let position = self.position();
+        let mut s = self.lock();
+        s.base = position;
+        s.anchor = Instant::now();
+        s.paused = paused;
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.lock().rate
+    }
+
+    pub fn set_rate(&self, rate: f64) {
+        let position = self.position();
+        let mut s = self.lock();
+        s.base = position;
+        s.anchor = Instant::now();
+        s.rate = rate;
+    }
+
+    pub fn stats(&self) -> SyncStats {
+        self.lock().stats
+    }
+
+    pub fn record_presented(&self, drift: f64, repeated: u64) {
+        let mut s = self.lock();
+        s.stats.presented += 1;
+        s.stats.repeated += repeated;
+        s.stats.drift = drift;
+    }
+
+    pub fn record_decoded(&self) {
+        self.lock().stats.decoded += 1;
+    }
+
+    pub fn record_dropped(&self) {
+        self.lock().stats.dropped += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn pause_freezes_and_rate_scales_position() {
+        let clock = PlaybackClock::default();
+        clock.set_position(10.0);
+        clock.set_paused(true);
+        let paused_at = clock.position();
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(clock.position(), paused_at);
+
+        // Bracket the clock's own reads with ours, so a slow machine can't break the bounds
+        clock.set_rate(2.0);
+        let before_resume = Instant::now();
+        clock.set_paused(false);
+        let resumed = Instant::now();
+        thread::sleep(Duration::from_millis(20));
+        let before_read = Instant::now();
+        let advanced = clock.position() - paused_at;
+        let read = Instant::now();
+
+        let min = 2.0 * (before_read - resumed).as_secs_f64();
+        let max = 2.0 * (read - before_resume).as_secs_f64();
+        assert!((min..=max).contains(&advanced), "advanced {advanced}");
+    }
+}
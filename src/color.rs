@@ -0,0 +1,120 @@
+use clap::ValueEnum;
+use ratatui::style::Color;
+
+/// How many colors the terminal can show, for `--color` video.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ColorDepth {
+    Truecolor,
+    #[value(name = "256")]
+    Ansi256,
+    #[value(name = "16")]
+    Ansi16,
+}
+
+/// The xterm defaults for the 16 ANSI colors, in `Color::Indexed` order.
+const ANSI16: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0, 0, 0]),
+    (Color::Red, [205, 0, 0]),
+    (Color::Green, [0, 205, 0]),
+    (Color::Yellow, [205, 205, 0]),
+    (Color::Blue, [0, 0, 238]),
+    (Color::Magenta, [205, 0, 205]),
+    (Color::Cyan, [0, 205, 205]),
+    (Color::Gray, [229, 229, 229]),
+    (Color::DarkGray, [127, 127, 127]),
+    (Color::LightRed, [255, 0, 0]),
+    (Color::LightGreen, [0, 255, 0]),
+    (Color::LightYellow, [255, 255, 0]),
+    (Color::LightBlue, [92, 92, 255]),
+    (Color::LightMagenta, [255, 0, 255]),
+    (Color::LightCyan, [0, 255, 255]),
+    (Color::White, [255, 255, 255]),
+];
+
+/// Channel values of the 6x6x6 cube in the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorDepth {
+    /// Guesses from `COLORTERM` and `TERM` the way most CLI tools do.
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Self::Truecolor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// The closest color this depth can show.
+    pub fn quantize(self, [r, g, b]: [u8; 3]) -> Color {
+        match self {
+            Self::Truecolor => Color::Rgb(r, g, b),
+            Self::Ansi256 => Color::Indexed(quantize_256([r, g, b])),
+            Self::Ansi16 => nearest(&ANSI16, [r, g, b]),
+        }
+    }
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(&x, y)| (x as i32 - y as i32).pow(2) as u32)
+        .sum()
+}
+
+fn nearest(palette: &[(Color, [u8; 3])], rgb: [u8; 3]) -> Color {
+    palette
+        .iter()
+        .min_by_key(|(_, p)| distance(*p, rgb))
+        .map_or(Color::Reset, |(c, _)| *c)
+}
+
+/// Picks the nearer of the closest cube entry (16-231) and gray ramp entry (232-255).
+fn quantize_256(rgb: [u8; 3]) -> u8 {
+    let level = |v: u8| {
+        (0..6)
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+            .unwrap()
+    };
+    let [r, g, b] = rgb.map(level);
+    let cube = [CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]];
+
+    let mean = rgb.iter().map(|&v| v as u32).sum::<u32>() / 3;
+    let gray_step = (mean.saturating_sub(8) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_step;
+
+    if distance([gray; 3], rgb) < distance(cube, rgb) {
+        232 + gray_step
+    } else {
+        (16 + 36 * r + 6 * g + b) as u8
+    }
+}
+
+/// Rec. 601 luma, so color frames keep the gray pipeline's shapes.
+pub fn luma([r, g, b]: [u8; 3]) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantizes_to_each_depth() {
+        let orange = [255, 135, 0];
+        assert_eq!(
+            ColorDepth::Truecolor.quantize(orange),
+            Color::Rgb(255, 135, 0)
+        );
+        assert_eq!(ColorDepth::Ansi256.quantize(orange), Color::Indexed(208));
+        assert_eq!(
+            ColorDepth::Ansi256.quantize([128, 128, 128]),
+            Color::Indexed(244)
+        );
+        assert_eq!(ColorDepth::Ansi16.quantize([250, 10, 10]), Color::LightRed);
+        assert_eq!(luma([255, 255, 255]), 255);
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Span the overlay's rates are averaged over.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Per-second rate of a growing counter, from samples over the last `RATE_WINDOW`.
+#[derive(Default)]
+pub struct RateMeter {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateMeter {
+    pub fn sample(&mut self, now: Instant, total: u64) {
+        // The counter starts over on a seek
+        if self.samples.back().is_some_and(|&(_, last)| total < last) {
+            self.samples.clear();
+        }
+        self.samples.push_back((now, total));
+        while self.samples.len() > 2 && now - self.samples[1].0 >= RATE_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn per_second(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(&(start, first)), Some(&(end, last))) if end > start => {
+                (last - first) as f64 / (end - start).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// State behind the FPS and sync overlay toggled with F3.
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub visible: bool,
+    draws: u64,
+    pub render: RateMeter,
+    pub decode: RateMeter,
+}
+
+impl DebugOverlay {
+    /// Counts a finished redraw, sampling the decoder's frame count alongside it.
+    pub fn on_draw(&mut self, decoded: u64) {
+        let now = Instant::now();
+        self.draws += 1;
+        self.render.sample(now, self.draws);
+        self.decode.sample(now, decoded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_covers_the_last_second_and_survives_resets() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut meter = RateMeter::default();
+        assert_eq!(meter.per_second(), 0.0);
+
+        // 10 per second for two seconds, then 30 per second
+        for i in 0..=20 {
+            meter.sample(at(i * 100), i);
+        }
+        assert!((meter.per_second() - 10.0).abs() < 1e-9);
+        for i in 1..=30 {
+            meter.sample(at(2000 + i * 100), 20 + 3 * i);
+        }
+        assert!((meter.per_second() - 30.0).abs() < 1e-9);
+
+        meter.sample(at(5100), 2);
+        assert_eq!(meter.per_second(), 0.0);
+        meter.sample(at(5200), 5);
+        assert!((meter.per_second() - 30.0).abs() < 1e-9);
+    }
+}
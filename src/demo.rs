@@ -0,0 +1,86 @@
+use crate::types::ScriptEntry;
+
+/// Which demo page is up as the video plays. The first entry's page shows from the start and
+/// every later one once playback reaches its timestamp. Both the app and the offline
+/// exporters step it with their clock, so recordings switch pages when playback would.
+#[derive(Default)]
+pub struct DemoTimeline {
+    entries: Vec<ScriptEntry>,
+    /// Index of the next entry to show
+    next: usize,
+}
+
+impl DemoTimeline {
+    pub fn new(entries: Vec<ScriptEntry>) -> Self {
+        Self { entries, next: 0 }
+    }
+
+    pub fn entries(&self) -> &[ScriptEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Starts over from the top, returning the entry to show first.
+    pub fn begin(&mut self) -> Option<usize> {
+        self.next = self.entries.len().min(1);
+        self.next.checked_sub(1)
+    }
+
+    /// The latest entry that came due by `position`, if any did since the last call.
+    pub fn advance(&mut self, position: f64) -> Option<usize> {
+        let due = self.entries[self.next..]
+            .iter()
+            .take_while(|e| e.timestamp <= position)
+            .count();
+        self.next += due;
+        (due > 0).then(|| self.next - 1)
+    }
+
+    /// Picks up after a seek to `position`, returning the entry whose page is due there.
+    pub fn seek(&mut self, position: f64) -> Option<usize> {
+        self.next = self
+            .entries
+            .iter()
+            .position(|e| e.timestamp > position)
+            .unwrap_or(self.entries.len());
+        self.next.checked_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_each_page_once_it_is_due() {
+        let entries = [1.0, 2.0, 2.5, 4.0]
+            .map(|timestamp| ScriptEntry {
+                timestamp,
+                url: timestamp.to_string(),
+            })
+            .to_vec();
+        let mut timeline = DemoTimeline::new(entries);
+
+        assert_eq!(timeline.begin(), Some(0));
+        assert_eq!(timeline.advance(0.5), None);
+        assert_eq!(timeline.advance(1.5), None);
+        // Entries that came due together skip straight to the last
+        assert_eq!(timeline.advance(3.0), Some(2));
+        assert_eq!(timeline.advance(3.5), None);
+
+        assert_eq!(timeline.seek(2.2), Some(1));
+        assert_eq!(timeline.advance(2.6), Some(2));
+        assert_eq!(timeline.seek(0.5), None);
+        assert_eq!(timeline.advance(10.0), Some(3));
+        assert_eq!(timeline.advance(11.0), None);
+
+        assert_eq!(DemoTimeline::default().begin(), None);
+    }
+}
@@ -0,0 +1,249 @@
+use clap::ValueEnum;
+use std::sync::OnceLock;
+
+/// How gray frames are reduced to the few shades the video mask can show.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Dither {
+    /// Round to the nearest shade
+    None,
+    /// Ordered dithering with an 8x8 Bayer matrix
+    Bayer,
+    /// Error diffusion
+    FloydSteinberg,
+    /// Ordered dithering with a 16x16 void-and-cluster threshold map
+    BlueNoise,
+}
+
+const BLUE_NOISE_SIZE: usize = 16;
+
+impl Dither {
+    /// The next method for the video mode toggle.
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Bayer,
+            Self::Bayer => Self::FloydSteinberg,
+            Self::FloydSteinberg => Self::BlueNoise,
+            Self::BlueNoise => Self::None,
+        }
+    }
+
+    /// Quantizes a `width x height` gray frame in place to `levels`, which must be sorted
+    /// ascending and start at 0 and end at 255.
+    pub fn apply(self, pixels: &mut [u8], width: usize, height: usize, levels: &[u8]) {
+        match self {
+            Self::None => {
+                for p in pixels.iter_mut() {
+                    *p = nearest_level(*p as f32, levels);
+                }
+            }
+            Self::Bayer => ordered(pixels, width, levels, |x, y| bayer(x % 8, y % 8)),
+            Self::BlueNoise => ordered(pixels, width, levels, |x, y| {
+                blue_noise()[(y % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + x % BLUE_NOISE_SIZE]
+            }),
+            Self::FloydSteinberg => floyd_steinberg(pixels, width, height, levels),
+        }
+    }
+}
+
+fn nearest_level(value: f32, levels: &[u8]) -> u8 {
+    *levels
+        .iter()
+        .min_by(|a, b| {
+            (**a as f32 - value)
+                .abs()
+                .total_cmp(&(**b as f32 - value).abs())
+        })
+        .unwrap()
+}
+
+/// Rounds each pixel up or down to the levels around it, up when its position between them
+/// exceeds the threshold map's value in `0..1`.
+fn ordered(
+    pixels: &mut [u8],
+    width: usize,
+    levels: &[u8],
+    threshold: impl Fn(usize, usize) -> f32,
+) {
+    for (i, p) in pixels.iter_mut().enumerate() {
+        let upper = levels.partition_point(|&l| l < *p).min(levels.len() - 1);
+        if upper == 0 || levels[upper] == *p {
+            continue;
+        }
+        let (lo, hi) = (levels[upper - 1], levels[upper]);
+        let t = (*p - lo) as f32 / (hi - lo) as f32;
+        *p = if t > threshold(i % width, i / width) {
+            hi
+        } else {
+            lo
+        };
+    }
+}
+
+/// Recursive Bayer matrix value at `(x, y)` in `0..8`, normalized to `0..1`.
+fn bayer(x: usize, y: usize) -> f32 {
+    let mut value = 0;
+    for bit in 0..3 {
+        let (xb, yb) = ((x >> bit) & 1, (y >> bit) & 1);
+        value |= (((xb ^ yb) << 1) | yb) << (2 * (2 - bit));
+    }
+    (value as f32 + 0.5) / 64.0
+}
+
+fn floyd_steinberg(pixels: &mut [u8], width: usize, height: usize, levels: &[u8]) {
+    let mut error = vec![0f32; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let value = (pixels[i] as f32 + error[i]).clamp(0.0, 255.0);
+            let out = nearest_level(value, levels);
+            pixels[i] = out;
+            let err = value - out as f32;
+            if x + 1 < width {
+                error[i + 1] += err * 7.0 / 16.0;
+            }
+            if y + 1 < height {
+                if x > 0 {
+                    error[i + width - 1] += err * 3.0 / 16.0;
+                }
+                error[i + width] += err * 5.0 / 16.0;
+                if x + 1 < width {
+                    error[i + width + 1] += err / 16.0;
+                }
+            }
+        }
+    }
+}
+
+/// Threshold map built once with Ulichney's void-and-cluster method: pixels are ranked by
+/// repeatedly filling the largest void of a toroidal pattern, so every threshold level is
+/// evenly spread.
+fn blue_noise() -> &'static [f32] {
+    static MAP: OnceLock<Vec<f32>> = OnceLock::new();
+    MAP.get_or_init(|| {
+        let n = BLUE_NOISE_SIZE;
+        let total = n * n;
+        let kernel: Vec<f32> = (0..total)
+            .map(|i| {
+                let wrap = |d: usize| d.min(n - d) as f32;
+                let (dx, dy) = (wrap(i % n), wrap(i / n));
+                (-(dx * dx + dy * dy) / (2.0 * 1.5 * 1.5)).exp()
+            })
+            .collect();
+
+        let mut pattern = vec![false; total];
+        let mut energy = vec![0f32; total];
+        let toggle = |pattern: &mut [bool], energy: &mut [f32], p: usize, on: bool| {
+            pattern[p] = on;
+            let sign = if on { 1.0 } else { -1.0 };
+            for (i, e) in energy.iter_mut().enumerate() {
+                let dx = (i % n + n - p % n) % n;
+                let dy = (i / n + n - p / n) % n;
+                *e += sign * kernel[dy * n + dx];
+            }
+        };
+        let extreme = |pattern: &[bool], energy: &[f32], on: bool, tightest: bool| {
+            (0..total)
+                .filter(|&i| pattern[i] == on)
+                .max_by(|&a, &b| {
+                    let ord = energy[a].total_cmp(&energy[b]);
+                    if tightest { ord } else { ord.reverse() }
+                })
+                .unwrap()
+        };
+
+        // Deterministic sparse seed, then relax it until the tightest cluster is the largest void
+        let mut seed = 0x2545_f491_u32;
+        for _ in 0..total / 10 {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let p = seed as usize % total;
+            if !pattern[p] {
+                toggle(&mut pattern, &mut energy, p, true);
+            }
+        }
+        loop {
+            let cluster = extreme(&pattern, &energy, true, true);
+            toggle(&mut pattern, &mut energy, cluster, false);
+            let void = extreme(&pattern, &energy, false, false);
+            toggle(&mut pattern, &mut energy, void, true);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut rank = vec![0usize; total];
+        let ones = pattern.iter().filter(|&&p| p).count();
+        let (mut p, mut e) = (pattern.clone(), energy.clone());
+        for r in (0..ones).rev() {
+            let cluster = extreme(&p, &e, true, true);
+            toggle(&mut p, &mut e, cluster, false);
+            rank[cluster] = r;
+        }
+        // With a Gaussian kernel the largest void among zeros is also their tightest
+        // cluster once they are the minority, so one loop fills the rest
+        for r in ones..total {
+            let void = extreme(&pattern, &energy, false, false);
+            toggle(&mut pattern, &mut energy, void, true);
+            rank[void] = r;
+        }
+        rank.iter()
+            .map(|&r| (r as f32 + 0.5) / total as f32)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: [u8; 2] = [0, 255];
+
+    /// A horizontal ramp: without dithering it collapses to two flat halves.
+    fn ramp() -> Vec<u8> {
+        (0..64 * 16).map(|i| (i % 64 * 4) as u8).collect()
+    }
+
+    /// Mean of the 8 columns centered on `x`.
+    fn band_mean(pixels: &[u8], x: usize) -> f32 {
+        let band = (0..16).flat_map(|y| (x - 4..x + 4).map(move |x| pixels[y * 64 + x]));
+        band.map(|p| p as f32).sum::<f32>() / 128.0
+    }
+
+    #[test]
+    fn dithering_preserves_gradient_brightness() {
+        for method in [Dither::Bayer, Dither::FloydSteinberg, Dither::BlueNoise] {
+            let mut pixels = ramp();
+            method.apply(&mut pixels, 64, 16, &LEVELS);
+            assert!(pixels.iter().all(|p| LEVELS.contains(p)), "{method:?}");
+            // A band a quarter of the way up the ramp is about a quarter lit
+            let quarter = band_mean(&pixels, 16);
+            assert!((40.0..=90.0).contains(&quarter), "{method:?}: {quarter}");
+        }
+
+        let mut pixels = ramp();
+        Dither::None.apply(&mut pixels, 64, 16, &LEVELS);
+        assert_eq!(band_mean(&pixels, 16), 0.0);
+    }
+
+    #[test]
+    fn threshold_maps_use_every_rank_once() {
+        let mut bayer: Vec<f32> = (0..64).map(|i| bayer(i % 8, i / 8)).collect();
+        bayer.sort_by(f32::total_cmp);
+        assert!(
+            bayer
+                .iter()
+                .enumerate()
+                .all(|(i, &t)| t == (i as f32 + 0.5) / 64.0)
+        );
+
+        let mut noise = blue_noise().to_vec();
+        noise.sort_by(f32::total_cmp);
+        assert!(
+            noise
+                .iter()
+                .enumerate()
+                .all(|(i, &t)| t == (i as f32 + 0.5) / 256.0)
+        );
+    }
+}
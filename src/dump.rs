@@ -0,0 +1,64 @@
+use crate::web::{LinkStyle, WebEngine, caption_images, extract_images, extract_title, parse_html};
+use anyhow::Result;
+use clap::ValueEnum;
+use reqwest::Url;
+use serde_json::json;
+use std::sync::mpsc;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum DumpFormat {
+    Text,
+    Json,
+}
+
+/// Fetches `url` and prints its rendered text, lynx `-dump` style, without touching the terminal.
+pub fn run(url: &str, width: usize, format: DumpFormat) -> Result<()> {
+    let (tx, _rx) = mpsc::sync_channel(1);
+    let web = WebEngine::new(tx);
+    let (final_url, html) = web.fetch_blocking(url)?;
+
+    let title = extract_title(&html);
+    let (html, images) = extract_images(&html);
+    let (text, _, _, links) = parse_html(&html, width, LinkStyle::References);
+    let text = caption_images(&text, &images);
+
+    let base = Url::parse(&final_url).ok();
+    let links: Vec<String> = links
+        .into_iter()
+        .map(|href| {
+            base.as_ref()
+                .and_then(|b| b.join(&href).ok())
+                .map(|u| u.to_string())
+                .unwrap_or(href)
+        })
+        .collect();
+
+    match format {
+        DumpFormat::Text => {
+            print!("{}", text.trim_end());
+            println!();
+            if !links.is_empty() {
+                println!("\nReferences\n");
+                let pad = links.len().to_string().len();
+                for (i, link) in links.iter().enumerate() {
+                    println!("{n:>pad$}. {link}", n = i + 1);
+                }
+            }
+        }
+        DumpFormat::Json => {
+            let value = json!({
+                "url": final_url,
+                "title": title,
+                "text": text,
+                "links": links,
+                "images": images
+                    .iter()
+                    .map(|i| json!({ "src": i.src, "alt": i.alt }))
+                    .collect::<Vec<_>>(),
+            });
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        }
+    }
+
+    Ok(())
+}
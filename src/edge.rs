@@ -0,0 +1,86 @@
+/// Sobel magnitude above which a pixel counts as part of a contour. A hard black/white step
+/// scores 1020.
+pub const EDGE_THRESHOLD: f32 = 200.0;
+
+/// Brightness gradient at one pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Gradient {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Gradient {
+    pub fn magnitude(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+
+    pub fn is_edge(self) -> bool {
+        self.magnitude() > EDGE_THRESHOLD
+    }
+
+    /// The line glyph running along the contour, which is perpendicular to the gradient.
+    /// Rows grow downwards, so a gradient pointing down-right means a `/` edge.
+    pub fn glyph(self) -> char {
+        let angle = self.y.atan2(self.x).to_degrees().rem_euclid(180.0);
+        match angle {
+            a if !(22.5..157.5).contains(&a) => '|',
+            a if a < 67.5 => '/',
+            a if a < 112.5 => '-',
+            _ => '\\',
+        }
+    }
+}
+
+/// 3x3 Sobel operator over a gray frame, repeating the border pixels.
+pub fn sobel(pixels: &[u8], width: usize, height: usize) -> Vec<Gradient> {
+    let at = |x: isize, y: isize| {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        pixels[y * width + x] as f32
+    };
+    let mut out = Vec::with_capacity(width * height);
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+            let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+            out.push(Gradient { x: gx, y: gy });
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Glyphs drawn for an 8x8 frame, `.` where there is no edge.
+    fn outline(pixels: &[u8]) -> Vec<String> {
+        let gradients = sobel(pixels, 8, 8);
+        gradients
+            .chunks(8)
+            .map(|row| {
+                row.iter()
+                    .map(|g| if g.is_edge() { g.glyph() } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_contours_and_their_orientation() {
+        let vertical: Vec<u8> = (0..64).map(|i| if i % 8 < 4 { 0 } else { 255 }).collect();
+        assert!(outline(&vertical).iter().all(|row| row == "...||..."));
+
+        let horizontal: Vec<u8> = (0..64).map(|i| if i / 8 < 4 { 0 } else { 255 }).collect();
+        assert_eq!(outline(&horizontal)[3], "--------");
+        assert_eq!(outline(&horizontal)[0], "........");
+
+        // Lit below the anti-diagonal: the contour runs bottom-left to top-right
+        let diagonal: Vec<u8> = (0..64)
+            .map(|i| if i % 8 + i / 8 > 7 { 255 } else { 0 })
+            .collect();
+        assert_eq!(outline(&diagonal)[4].chars().nth(3), Some('/'));
+    }
+}
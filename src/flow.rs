@@ -0,0 +1,141 @@
+use unicode_width::UnicodeWidthChar;
+
+fn width(ch: char) -> usize {
+    UnicodeWidthChar::width(ch).unwrap_or(1)
+}
+
+/// Cells taken by a char and the zero-width marks after it. Never 0, so a stray combining
+/// mark still moves the layout along.
+pub fn cluster_width(cluster: &[char]) -> usize {
+    width(cluster[0]).max(1)
+}
+
+/// Splits `chars` into clusters of one char and the zero-width chars (combining marks,
+/// joiners) that follow it, so each cluster draws into one cell.
+fn clusters(chars: &[char]) -> impl Iterator<Item = &[char]> {
+    let mut rest = chars;
+    std::iter::from_fn(move || {
+        let marks = rest
+            .get(1..)?
+            .iter()
+            .take_while(|&&c| width(c) == 0)
+            .count();
+        let (cluster, tail) = rest.split_at(1 + marks);
+        rest = tail;
+        Some(cluster)
+    })
+}
+
+/// The next word at or after `*idx` in the cyclic `text`: its start and length in chars
+/// and its width in cells. Text wrapping around ends a word.
+fn next_word(text: &[char], idx: usize) -> Option<(usize, usize, usize)> {
+    let start = (0..text.len())
+        .map(|i| (idx + i) % text.len())
+        .find(|&i| !text[i].is_whitespace())?;
+    let len = text[start..]
+        .iter()
+        .take_while(|c| !c.is_whitespace())
+        .count();
+    let cells = clusters(&text[start..start + len]).map(cluster_width).sum();
+    Some((start, len, cells))
+}
+
+/// Lays out whole words from `text`, starting at `*text_idx`, into each run of `true` in
+/// `lit`, like wrapping text inside a shape. A word that doesn't fit the rest of a run is
+/// carried over to the next one; only words wider than `longest_run` are broken.
+///
+/// Returns one entry per cell: the char starting there with any zero-width marks on it, or
+/// `None` for a blank or for the second cell of a wide char.
+pub fn flow_words<'a>(
+    lit: &[bool],
+    text: &'a [char],
+    text_idx: &mut usize,
+    longest_run: usize,
+) -> Vec<Option<&'a [char]>> {
+    let mut cells = vec![None; lit.len()];
+    let mut x = 0;
+    while x < lit.len() {
+        if !lit[x] {
+            x += 1;
+            continue;
+        }
+        let run_end = x + lit[x..].iter().take_while(|&&l| l).count();
+        let mut col = x;
+        while let Some((start, len, word_width)) = next_word(text, *text_idx) {
+            let gap = usize::from(col > x);
+            if col + gap + word_width <= run_end {
+                col += gap;
+                for cluster in clusters(&text[start..start + len]) {
+                    cells[col] = Some(cluster);
+                    col += cluster_width(cluster);
+                }
+                *text_idx = (start + len) % text.len();
+            } else if col == x && word_width > longest_run {
+                // Can never fit whole, so fill the run with as much of it as fits
+                let mut used = 0;
+                for cluster in clusters(&text[start..start + len]) {
+                    if col + cluster_width(cluster) > run_end {
+                        break;
+                    }
+                    cells[col] = Some(cluster);
+                    col += cluster_width(cluster);
+                    used += cluster.len();
+                }
+                *text_idx = (start + used) % text.len();
+                break;
+            } else {
+                break;
+            }
+        }
+        x = run_end;
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(lit: &str, text: &str, idx: &mut usize, longest: usize) -> String {
+        let lit: Vec<bool> = lit.chars().map(|c| c == '#').collect();
+        let text: Vec<char> = text.chars().collect();
+        flow_words(&lit, &text, idx, longest)
+            .iter()
+            .map(|c| c.unwrap_or(&[' ']).iter().collect::<String>())
+            .collect()
+    }
+
+    #[test]
+    fn carries_words_that_do_not_fit_to_the_next_run() {
+        let mut idx = 0;
+        let text = "bad apple is a song ";
+        assert_eq!(
+            layout("#########..####...##", text, &mut idx, 9),
+            "bad apple  is a     "
+        );
+        assert_eq!(idx, 14);
+        // The next row picks up where this one stopped and wraps around the text
+        assert_eq!(layout("########", text, &mut idx, 9), "song bad");
+    }
+
+    #[test]
+    fn breaks_only_words_wider_than_any_run() {
+        let mut idx = 0;
+        assert_eq!(layout("###.###", "abcdefgh x", &mut idx, 3), "abc def");
+        assert_eq!(idx, 6);
+    }
+
+    #[test]
+    fn combining_marks_share_the_cell_before_them() {
+        // "café" with a decomposed é exactly fills its run, with room left in the second
+        let text = "cafe\u{301} ";
+        let mut idx = 0;
+        assert_eq!(layout("####", text, &mut idx, 6), text.trim_end());
+        assert_eq!(idx, 5);
+        assert_eq!(layout("######", text, &mut idx, 6), "cafe\u{301}  ");
+
+        // A stray mark at the start of a word still takes a cell of its own
+        let mut idx = 0;
+        assert_eq!(layout("###", "\u{301}ab ", &mut idx, 3), "\u{301}ab");
+    }
+}
@@ -0,0 +1,159 @@
+use crate::dither::Dither;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+/// Set in `FrameBuffer::middle` while it holds a frame the reader hasn't taken yet.
+const FRESH: usize = 4;
+
+/// One decoded frame and the geometry it was decoded at.
+#[derive(Default)]
+pub struct VideoFrame {
+    pub pixels: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    /// Frame pixels per terminal cell, across and down
+    pub cell_pixels: (usize, usize),
+    /// When the playback thread handed the frame over
+    pub published: Option<Instant>,
+    /// `pixels` dithered with the method and levels in `dithered_for`, made on first draw
+    dithered: Vec<u8>,
+    dithered_for: Option<(Dither, Vec<u8>)>,
+}
+
+impl VideoFrame {
+    /// The pixels dithered down to `levels`, computed once per frame, method and levels so
+    /// redraws of a paused frame don't dither it again.
+    pub fn dithered(&mut self, dither: Dither, levels: &[u8]) -> &[u8] {
+        let stale = self
+            .dithered_for
+            .as_ref()
+            .is_none_or(|(d, l)| *d != dither || l != levels);
+        if stale {
+            self.dithered.clear();
+            self.dithered.extend_from_slice(&self.pixels);
+            dither.apply(&mut self.dithered, self.width, self.height, levels);
+            self.dithered_for = Some((dither, levels.to_vec()));
+        }
+        &self.dithered
+    }
+}
+
+/// Triple buffer handing frames from the playback thread to the UI without copying them
+/// under a shared lock. The writer fills its back slot and swaps it with the middle one;
+/// the reader swaps the middle slot with its front one only when a new frame is there.
+/// Each slot belongs to one side at a time, so its mutex is never contended.
+///
+/// Frames may be published from any thread, but only the UI thread may read.
+pub struct FrameBuffer {
+    slots: [Mutex<VideoFrame>; 3],
+    /// Index of the writer's slot; locked while publishing so pipelines being replaced
+    /// can't write at the same time
+    back: Mutex<usize>,
+    /// Index of the slot in between, or'ed with `FRESH`
+    middle: AtomicUsize,
+    /// Index of the reader's slot
+    front: AtomicUsize,
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self {
+            slots: Default::default(),
+            back: Mutex::new(0),
+            middle: AtomicUsize::new(1),
+            front: AtomicUsize::new(2),
+        }
+    }
+}
+
+impl FrameBuffer {
+    /// Copies a frame into the back slot, reusing its allocation, and makes it the latest.
+    pub fn publish(&self, pixels: &[u8], width: usize, height: usize, cell_pixels: (usize, usize)) {
+        let mut back = self.back.lock().unwrap();
+        {
+            let mut slot = self.slots[*back].lock().unwrap();
+            slot.pixels.clear();
+            slot.pixels.extend_from_slice(pixels);
+            slot.width = width;
+            slot.height = height;
+            slot.cell_pixels = cell_pixels;
+            slot.published = Some(Instant::now());
+            slot.dithered_for = None;
+        }
+        *back = self.middle.swap(*back | FRESH, Ordering::AcqRel) & !FRESH;
+    }
+
+    /// Whether a frame was published since the last `latest`.
+    pub fn has_new(&self) -> bool {
+        self.middle.load(Ordering::Acquire) & FRESH != 0
+    }
+
+    /// The most recently published frame, empty before the first one.
+    pub fn latest(&self) -> MutexGuard<'_, VideoFrame> {
+        let mut front = self.front.load(Ordering::Relaxed);
+        if self.has_new() {
+            front = self.middle.swap(front, Ordering::AcqRel) & !FRESH;
+            self.front.store(front, Ordering::Relaxed);
+        }
+        self.slots[front].lock().unwrap()
+    }
+
+    /// When the frame last returned by `latest` was published. Unlike `latest` it never takes
+    /// a newer frame, so reading it can't skip one that hasn't been drawn.
+    pub fn front_published(&self) -> Option<Instant> {
+        self.slots[self.front.load(Ordering::Relaxed)]
+            .lock()
+            .unwrap()
+            .published
+    }
+
+    /// Empties every slot, so the UI shows the buffering notice until the next frame.
+    pub fn clear(&self) {
+        let _back = self.back.lock().unwrap();
+        for slot in &self.slots {
+            let mut slot = slot.lock().unwrap();
+            slot.pixels.clear();
+            slot.published = None;
+        }
+        self.middle.fetch_or(FRESH, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reader_gets_the_latest_frame_without_blocking_the_writer() {
+        let frames = FrameBuffer::default();
+        assert!(frames.latest().pixels.is_empty());
+
+        frames.publish(&[1], 1, 1, (1, 1));
+        frames.publish(&[2, 2], 2, 1, (1, 1));
+        assert!(frames.has_new());
+        {
+            // The writer never touches the slot being drawn, or this would deadlock
+            let front = frames.latest();
+            assert_eq!(front.pixels, [2, 2]);
+            assert!(!frames.has_new());
+            for i in 3..6 {
+                frames.publish(&[i], 1, 1, (1, 1));
+            }
+            assert_eq!(front.pixels, [2, 2]);
+        }
+        let drawn = {
+            let front = frames.latest();
+            assert_eq!(front.pixels, [5]);
+            front.published
+        };
+        frames.publish(&[6], 1, 1, (1, 1));
+        assert_eq!(frames.front_published(), drawn);
+        assert!(frames.has_new());
+        assert_eq!(frames.latest().pixels, [6]);
+
+        frames.clear();
+        assert!(frames.has_new());
+        assert!(frames.latest().pixels.is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+use crate::color::ColorDepth;
+use crate::demo::DemoTimeline;
+use crate::dither::Dither;
+use crate::source::{FixedRate, SourceSpec};
+use crate::theme::Theme;
+use crate::types::{RenderMode, ScriptEntry};
+use crate::ui::{MaskFrame, VideoMask, mask_levels};
+use crate::video::fit_frame;
+use crate::web::{Page, WebEngine};
+use anyhow::{Context, Result};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier, Style};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use unicode_width::UnicodeWidthStr;
+
+pub struct HeadlessOptions {
+    pub video_path: String,
+    pub start_url: String,
+    pub demo: Vec<ScriptEntry>,
+    pub width: u16,
+    pub height: u16,
+    pub fps: f64,
+    pub duration: Option<f64>,
+    pub cell_aspect: f64,
+    pub render_mode: RenderMode,
+    /// Depth to show color at, for videos ffmpeg decodes
+    pub color: Option<ColorDepth>,
+    pub dither: Dither,
+    pub theme: Theme,
+}
+
+/// Stand-in for the wall clock: frame `n` is presented at exactly `n / fps` seconds.
+pub struct SimulatedClock {
+    fps: f64,
+    frame: u64,
+}
+
+impl SimulatedClock {
+    pub fn new(fps: f64) -> Self {
+        Self { fps, frame: 0 }
+    }
+
+    pub fn now(&self) -> f64 {
+        self.frame as f64 / self.fps
+    }
+
+    pub fn tick(&mut self) {
+        self.frame += 1;
+    }
+}
+
+/// Decodes the whole video at a fixed rate, sized as playback would, and renders every frame with the demo page that
+/// playback shows at that time, stepping the app's `DemoTimeline` on the simulated clock.
+/// Returns the number of frames.
+pub fn render_frames(
+    opts: &HeadlessOptions,
+    mut on_frame: impl FnMut(f64, &Buffer) -> Result<()>,
+) -> Result<usize> {
+    let pages = load_pages(opts)?;
+    let mut demo = DemoTimeline::new(opts.demo.clone());
+    let page = |index: Option<usize>| index.map(|i| &pages[&opts.demo[i].url]);
+    let mut dense_text = page(demo.begin()).unwrap_or(&pages[&opts.start_url]);
+
+    let spec = SourceSpec::parse(&opts.video_path)?;
+    let term = (opts.width as usize, opts.height as usize);
+    let (cells_w, cells_h) = spec
+        .native_size()
+        .map_or(term, |size| fit_frame(term, size, opts.cell_aspect));
+    let (nx, ny) = opts.render_mode.cell_pixels();
+    let (w, h) = (cells_w * nx, cells_h * ny);
+    let color = opts.color.filter(|_| spec.decodes_color(true));
+    let mut frames = FixedRate::new(spec.open(w, h, 0.0, 1.0, color.is_some())?, opts.fps)?;
+    // Prerendered frames keep their own size at one pixel per cell
+    let (cell_pixels, (w, h)) = match frames.size() {
+        size if size == (w, h) => ((nx, ny), size),
+        size => ((1, 1), size),
+    };
+
+    let mut terminal = Terminal::new(TestBackend::new(opts.width, opts.height))?;
+    let mut clock = SimulatedClock::new(opts.fps);
+    let mut pixels = Vec::with_capacity(w * h);
+    let mut count = 0;
+
+    while opts.duration.is_none_or(|d| clock.now() < d)
+        && let Some(frame) = frames.next_frame()?
+    {
+        let now = clock.now();
+        pixels.clear();
+        pixels.extend_from_slice(frame);
+        if let Some(text) = page(demo.advance(now)) {
+            dense_text = text;
+        }
+
+        if color.is_none()
+            && opts.dither != Dither::None
+            && let Some(levels) = mask_levels(&opts.theme, opts.render_mode)
+        {
+            opts.dither.apply(&mut pixels, w, h, &levels);
+        }
+        let frame = MaskFrame {
+            pixels: &pixels,
+            width: w,
+            height: h,
+            cell_pixels,
+            color,
+        };
+        let mask = VideoMask {
+            frame: &frame,
+            dense_text,
+            scroll_y: 0,
+            render_mode: opts.render_mode,
+            theme: &opts.theme,
+        };
+        terminal.draw(|f| f.render_widget(mask, f.area()))?;
+        on_frame(now, terminal.backend().buffer())?;
+
+        count += 1;
+        clock.tick();
+    }
+    Ok(count)
+}
+
+fn load_pages(opts: &HeadlessOptions) -> Result<HashMap<String, Vec<char>>> {
+    let (tx, _rx) = mpsc::sync_channel(1);
+    let web = WebEngine::new(tx);
+
+    let mut pages = HashMap::new();
+    let urls = std::iter::once(&opts.start_url).chain(opts.demo.iter().map(|e| &e.url));
+    for url in urls {
+        if pages.contains_key(url) {
+            continue;
+        }
+        let (_, html) = web
+            .fetch_blocking(url)
+            .with_context(|| format!("failed to load {url}"))?;
+        pages.insert(url.clone(), Page::build(&html).dense_text);
+    }
+    Ok(pages)
+}
+
+/// Writes every frame to `out_dir/frame_NNNNNN.txt`, as plain text or with ANSI styling.
+pub fn run(opts: &HeadlessOptions, out_dir: &Path, ansi: bool) -> Result<()> {
+    fs::create_dir_all(out_dir)?;
+    let mut index = 0;
+    let count = render_frames(opts, |_, buffer| {
+        index += 1;
+        let path = out_dir.join(format!("frame_{index:06}.txt"));
+        let content = if ansi {
+            buffer_to_ansi(buffer)
+        } else {
+            buffer_to_text(buffer)
+        };
+        fs::write(path, content)?;
+        Ok(())
+    })?;
+    println!("Wrote {count} frames to {}", out_dir.display());
+    Ok(())
+}
+
+/// Iterates rows of `(symbol, style)`, skipping the filler cells after wide glyphs.
+fn visible_cells(buffer: &Buffer) -> impl Iterator<Item = Vec<(&str, Style)>> {
+    let area = buffer.area;
+    (area.top()..area.bottom()).map(move |y| {
+        let mut row = Vec::with_capacity(area.width as usize);
+        let mut skip = 0;
+        for x in area.left()..area.right() {
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            let cell = &buffer[(x, y)];
+            skip = cell.symbol().width().saturating_sub(1);
+            row.push((cell.symbol(), cell.style()));
+        }
+        row
+    })
+}
+
+pub fn buffer_to_text(buffer: &Buffer) -> String {
+    visible_cells(buffer)
+        .map(|row| {
+            let line: String = row.iter().map(|(symbol, _)| *symbol).collect();
+            line.trim_end().to_string() + "\n"
+        })
+        .collect()
+}
+
+/// Serializes the buffer as SGR-styled lines, resetting at the end of each row.
+pub fn buffer_to_ansi(buffer: &Buffer) -> String {
+    let mut out = String::new();
+    for row in visible_cells(buffer) {
+        let mut current = None;
+        for (symbol, style) in row {
+            if current != Some(style) {
+                out.push_str(&sgr(style));
+                current = Some(style);
+            }
+            out.push_str(symbol);
+        }
+        out.push_str("\x1b[0m\r\n");
+    }
+    out
+}
+
+fn sgr(style: Style) -> String {
+    let mut codes = vec!["0".to_string()];
+    let modifiers = [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::SLOW_BLINK, "5"),
+        (Modifier::REVERSED, "7"),
+    ];
+    for (modifier, code) in modifiers {
+        if style.add_modifier.contains(modifier) {
+            codes.push(code.to_string());
+        }
+    }
+    if let Some(fg) = style.fg.and_then(|c| color_code(c, false)) {
+        codes.push(fg);
+    }
+    if let Some(bg) = style.bg.and_then(|c| color_code(c, true)) {
+        codes.push(bg);
+    }
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+fn color_code(color: Color, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    let code = match color {
+        Color::Reset => return None,
+        Color::Black => base,
+        Color::Red => base + 1,
+        Color::Green => base + 2,
+        Color::Yellow => base + 3,
+        Color::Blue => base + 4,
+        Color::Magenta => base + 5,
+        Color::Cyan => base + 6,
+        Color::Gray => base + 7,
+        Color::DarkGray => base + 60,
+        Color::LightRed => base + 61,
+        Color::LightGreen => base + 62,
+        Color::LightYellow => base + 63,
+        Color::LightBlue => base + 64,
+        Color::LightMagenta => base + 65,
+        Color::LightCyan => base + 66,
+        Color::White => base + 67,
+        Color::Indexed(i) => return Some(format!("{};5;{i}", base + 8)),
+        Color::Rgb(r, g, b) => return Some(format!("{};2;{r};{g};{b}", base + 8)),
+    };
+    Some(code.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(mode: RenderMode) -> Buffer {
+        // Left half black, right half white
+        render_themed(
+            mode,
+            &Theme::classic(),
+            |x, w| if x < w / 2 { 0 } else { 255 },
+        )
+    }
+
+    fn render_themed(
+        mode: RenderMode,
+        theme: &Theme,
+        shade: impl Fn(usize, usize) -> u8,
+    ) -> Buffer {
+        let (nx, ny) = mode.cell_pixels();
+        let (w, h) = (8 * nx, 4 * ny);
+        let pixels: Vec<u8> = (0..w * h).map(|i| shade(i % w, w)).collect();
+        let frame = MaskFrame {
+            pixels: &pixels,
+            width: w,
+            height: h,
+            cell_pixels: (nx, ny),
+            color: None,
+        };
+        let text: Vec<char> = "Bad Apple!! ".chars().collect();
+
+        let mut terminal = Terminal::new(TestBackend::new(8, 4)).unwrap();
+        let mask = VideoMask {
+            frame: &frame,
+            dense_text: &text,
+            scroll_y: 0,
+            render_mode: mode,
+            theme,
+        };
+        terminal.draw(|f| f.render_widget(mask, f.area())).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    #[test]
+    fn cast_mode_golden_frame() {
+        assert_eq!(
+            buffer_to_text(&render(RenderMode::Cast)),
+            "    Appl\n    Bad\n    e!!\n    Appl\n"
+        );
+    }
+
+    #[test]
+    fn fit_mode_golden_frame() {
+        assert_eq!(
+            buffer_to_text(&render(RenderMode::Fit)),
+            "    Bad\n    Appl\n    e!!\n    Bad\n"
+        );
+    }
+
+    #[test]
+    fn flow_mode_golden_frame() {
+        assert_eq!(
+            buffer_to_text(&render(RenderMode::Flow)),
+            "    Bad\n    Appl\n    e!!\n    Bad\n"
+        );
+    }
+
+    #[test]
+    fn inverted_theme_writes_text_over_dark_pixels() {
+        let inverted = &Theme::builtin()[1];
+        for mode in [RenderMode::Fit, RenderMode::Flow] {
+            let text = buffer_to_text(&render_themed(mode, inverted, |_, _| 0));
+            assert!(text.contains("Bad"), "{mode:?}: {text:?}");
+        }
+    }
+
+    #[test]
+    fn outline_mode_draws_contour_glyphs() {
+        assert_eq!(
+            buffer_to_text(&render(RenderMode::Outline)),
+            "Bad||ppl\ne!!||ad\nApp||!!\nBad||ppl\n"
+        );
+    }
+
+    #[test]
+    fn sub_cell_modes_fill_the_lit_half() {
+        assert_eq!(
+            buffer_to_text(&render(RenderMode::HalfBlock)),
+            "    ████\n".repeat(4)
+        );
+        assert_eq!(
+            buffer_to_text(&render(RenderMode::Braille)),
+            "    ⣿⣿⣿⣿\n".repeat(4)
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use crate::utils::{data_dir, log_msg};
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const HISTORY_FILE: &str = "history.jsonl";
+/// How many recent visits are sampled for frecency, like Firefox's places database.
+const SAMPLED_VISITS: usize = 10;
+const DAY: i64 = 24 * 60 * 60;
+
+/// One line of the on-disk visit log.
+#[derive(Serialize, Deserialize)]
+struct Visit {
+    url: String,
+    title: String,
+    time: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: String,
+    pub visit_count: u32,
+    pub last_visit: i64,
+    recent_visits: Vec<i64>,
+}
+
+impl HistoryEntry {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            title: String::new(),
+            visit_count: 0,
+            last_visit: 0,
+            recent_visits: Vec::new(),
+        }
+    }
+
+    fn add_visit(&mut self, title: &str, time: i64) {
+        if !title.is_empty() {
+            self.title = title.to_string();
+        }
+        self.visit_count += 1;
+        self.last_visit = self.last_visit.max(time);
+        self.recent_visits.push(time);
+        if self.recent_visits.len() > SAMPLED_VISITS {
+            self.recent_visits.remove(0);
+        }
+    }
+
+    pub fn frecency(&self, now: i64) -> f64 {
+        if self.recent_visits.is_empty() {
+            return 0.0;
+        }
+        let points: u32 = self
+            .recent_visits
+            .iter()
+            .map(|&t| match (now - t) / DAY {
+                0..=4 => 100,
+                5..=14 => 70,
+                15..=31 => 50,
+                32..=90 => 30,
+                _ => 10,
+            })
+            .sum();
+        self.visit_count as f64 * points as f64 / self.recent_visits.len() as f64
+    }
+}
+
+/// Every page visit, appended to a JSON-lines log and aggregated per URL on load.
+#[derive(Default)]
+pub struct HistoryDb {
+    entries: HashMap<String, HistoryEntry>,
+    path: Option<PathBuf>,
+}
+
+impl HistoryDb {
+    pub fn load() -> Self {
+        let path = data_dir().join(HISTORY_FILE);
+        let mut db = Self::default();
+        if let Ok(content) = fs::read_to_string(&path) {
+            for line in content.lines() {
+                if let Ok(visit) = serde_json::from_str::<Visit>(line) {
+                    db.apply(&visit);
+                }
+            }
+        }
+        db.path = Some(path);
+        db
+    }
+
+    fn apply(&mut self, visit: &Visit) {
+        self.entries
+            .entry(visit.url.clone())
+            .or_insert_with(|| HistoryEntry::new(visit.url.clone()))
+            .add_visit(&visit.title, visit.time);
+    }
+
+    pub fn record_visit(&mut self, url: &str, title: &str) {
+        if url.is_empty() {
+            return;
+        }
+        let visit = Visit {
+            url: url.to_string(),
+            title: title.to_string(),
+            time: Utc::now().timestamp(),
+        };
+        self.apply(&visit);
+
+        if let Some(path) = &self.path
+            && let Err(e) = append_visit(path, &visit)
+        {
+            log_msg("error", &format!("History: failed to record visit: {e}"));
+        }
+    }
+
+    /// Entries whose URL or title contains every whitespace-separated term, best first.
+    pub fn complete(&self, query: &str, limit: usize) -> Vec<&HistoryEntry> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let now = Utc::now().timestamp();
+
+        let mut matches: Vec<(&HistoryEntry, f64)> = self
+            .entries
+            .values()
+            .filter(|e| {
+                let url = e.url.to_lowercase();
+                let title = e.title.to_lowercase();
+                terms
+                    .iter()
+                    .all(|t| url.contains(t.as_str()) || title.contains(t.as_str()))
+            })
+            .map(|e| (e, e.frecency(now)))
+            .collect();
+
+        matches.sort_by(|a, b| {
+            b.1.total_cmp(&a.1)
+                .then_with(|| b.0.last_visit.cmp(&a.0.last_visit))
+        });
+        matches.into_iter().take(limit).map(|(e, _)| e).collect()
+    }
+}
+
+fn append_visit(path: &Path, visit: &Visit) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(visit)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visit(db: &mut HistoryDb, url: &str, title: &str, days_ago: i64) {
+        db.apply(&Visit {
+            url: url.to_string(),
+            title: title.to_string(),
+            time: Utc::now().timestamp() - days_ago * DAY,
+        });
+    }
+
+    #[test]
+    fn ranks_frequent_recent_visits_first() {
+        let mut db = HistoryDb::default();
+        visit(
+            &mut db,
+            "https://a.example/wiki/Reimu",
+            "Reimu Hakurei",
+            200,
+        );
+        visit(
+            &mut db,
+            "https://a.example/wiki/Reimu",
+            "Reimu Hakurei",
+            200,
+        );
+        visit(
+            &mut db,
+            "https://a.example/wiki/Marisa",
+            "Marisa Kirisame",
+            1,
+        );
+        visit(
+            &mut db,
+            "https://a.example/wiki/Marisa",
+            "Marisa Kirisame",
+            0,
+        );
+        visit(&mut db, "https://b.example/", "Other", 0);
+
+        let urls: Vec<&str> = db
+            .complete("a.example wiki", 10)
+            .iter()
+            .map(|e| e.url.as_str())
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                "https://a.example/wiki/Marisa",
+                "https://a.example/wiki/Reimu"
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_titles_case_insensitively() {
+        let mut db = HistoryDb::default();
+        visit(&mut db, "https://a.example/1", "Bad Apple!!", 0);
+
+        assert_eq!(db.complete("bad APPLE", 10).len(), 1);
+        assert_eq!(db.complete("apple cider", 10).len(), 0);
+    }
+}
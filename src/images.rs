@@ -0,0 +1,150 @@
+use crate::ui::brightness_level;
+use anyhow::{Context, Result, bail};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// One glyph per `ui::brightness_level` bucket, darkest first.
+const ASCII_RAMP: [char; 4] = [' ', '.', '+', '#'];
+const MAX_IMAGE_ROWS: usize = 40;
+
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Decodes any image ffmpeg understands into 8-bit gray, at most `max_width` pixels wide.
+pub fn decode_gray(bytes: Vec<u8>, max_width: usize) -> Result<GrayImage> {
+    let scale = format!("scale='min({max_width},iw)':-1");
+    let mut child = Command::new("ffmpeg")
+        .args([
+            "-v",
+            "quiet",
+            "-i",
+            "pipe:0",
+            "-frames:v",
+            "1",
+            "-vf",
+            &scale,
+            "-pix_fmt",
+            "gray",
+            "-c:v",
+            "pgm",
+            "-f",
+            "image2pipe",
+            "-",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to spawn ffmpeg")?;
+
+    // Feed stdin from another thread so a full stdout pipe can't deadlock us.
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(&bytes);
+    });
+
+    let mut out = Vec::new();
+    child.stdout.take().unwrap().read_to_end(&mut out)?;
+    let _ = writer.join();
+    child.wait()?;
+
+    parse_pgm(&out)
+}
+
+pub fn parse_pgm(data: &[u8]) -> Result<GrayImage> {
+    let mut fields = Vec::with_capacity(4);
+    let mut pos = 0;
+    while fields.len() < 4 {
+        while pos < data.len() && data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if data.get(pos) == Some(&b'#') {
+            while pos < data.len() && data[pos] != b'\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            bail!("truncated PGM header");
+        }
+        fields.push(std::str::from_utf8(&data[start..pos])?);
+    }
+    // Exactly one whitespace byte separates the header from the raster
+    pos += 1;
+
+    if fields[0] != "P5" {
+        bail!("not a binary PGM");
+    }
+    let width: usize = fields[1].parse()?;
+    let height: usize = fields[2].parse()?;
+    let pixels = data
+        .get(pos..pos + width * height)
+        .context("truncated PGM")?;
+
+    Ok(GrayImage {
+        width,
+        height,
+        pixels: pixels.to_vec(),
+    })
+}
+
+/// Renders the image as text, halving rows to compensate for tall terminal cells.
+pub fn to_ascii(image: &GrayImage) -> Vec<String> {
+    if image.width == 0 || image.height == 0 {
+        return Vec::new();
+    }
+
+    let mut cols = image.width;
+    let mut rows = (image.height / 2).max(1);
+    if rows > MAX_IMAGE_ROWS {
+        cols = (cols * MAX_IMAGE_ROWS / rows).max(1);
+        rows = MAX_IMAGE_ROWS;
+    }
+
+    (0..rows)
+        .map(|y| {
+            let sy = y * image.height / rows;
+            (0..cols)
+                .map(|x| {
+                    let sx = x * image.width / cols;
+                    ASCII_RAMP[brightness_level(image.pixels[sy * image.width + sx])]
+                })
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pgm_with_comments() {
+        let mut data = b"P5\n# made by ffmpeg\n3 2\n255\n".to_vec();
+        data.extend_from_slice(&[0, 128, 255, 255, 128, 0]);
+
+        let image = parse_pgm(&data).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixels, [0, 128, 255, 255, 128, 0]);
+    }
+
+    #[test]
+    fn ascii_uses_mask_brightness_buckets() {
+        let image = GrayImage {
+            width: 4,
+            height: 2,
+            pixels: vec![0, 60, 150, 250, 0, 60, 150, 250],
+        };
+        assert_eq!(to_ascii(&image), [" .+#"]);
+    }
+}
@@ -1,6 +1,28 @@
 mod app;
+mod audio;
+mod bench;
+mod bookmarks;
+mod buffer;
+mod cast;
+mod clock;
+mod color;
+mod debug;
+mod demo;
+mod dither;
+mod dump;
+mod edge;
+mod flow;
+mod frame;
+mod headless;
+mod history;
 mod i18n;
+mod images;
+mod playlist;
+mod prerender;
+mod session;
+mod source;
 mod text;
+mod theme;
 mod types;
 mod ui;
 mod utils;
@@ -18,80 +40,182 @@ use crossterm::{
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use std::io;
-use std::time::Duration;
-use types::{AutoScroll, ScriptEntry};
+use std::time::{Duration, Instant};
+use types::{AutoScroll, RenderMode};
 
 const DEFAULT_URL: &str = "https://en.touhouwiki.net/wiki/Bad_Apple!!";
+/// How long to wait for input before checking for new frames and background events.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// Redraw at least this often so the status bar's clock keeps moving when nothing else does.
+const IDLE_REDRAW: Duration = Duration::from_millis(250);
 
 #[derive(Parser)]
 #[command(author, version, about)]
 struct Cli {
-    #[arg(long, default_value = "bad_apple.mp4")]
-    video: String,
+    /// Videos to play in order, or M3U playlists of them
+    #[arg(long, num_args = 1.., default_value = "bad_apple.mp4")]
+    video: Vec<String>,
+    /// What to play when a video ends
+    #[arg(long, value_enum, default_value_t = playlist::PlaylistMode::Once)]
+    playlist_mode: playlist::PlaylistMode,
     #[arg(long, default_value = DEFAULT_URL)]
     start_url: String,
+    /// Demo script for every video; without it each video uses the .demo file next to it
     #[arg(long)]
     demo: Option<String>,
     #[arg(long, env = "BAD_BROWSER_LOCALE")]
     lang: Option<String>,
+    /// Merge bookmarks from a Netscape bookmarks HTML file and exit
+    #[arg(long, value_name = "FILE")]
+    import_bookmarks: Option<String>,
+    /// Write bookmarks as a Netscape bookmarks HTML file and exit
+    #[arg(long, value_name = "FILE")]
+    export_bookmarks: Option<String>,
+    /// Reopen the buffers saved in the session instead of --start-url
+    #[arg(long)]
+    restore: bool,
+    /// Name of the session to save to (and restore from with --restore)
+    #[arg(long, value_name = "NAME", default_value = session::DEFAULT_SESSION)]
+    session: String,
+    /// Print the rendered text of URL to stdout and exit
+    #[arg(long, value_name = "URL")]
+    dump: Option<String>,
+    /// Wrap width for --dump
+    #[arg(long, default_value_t = 80)]
+    width: usize,
+    /// Output format for --dump
+    #[arg(long, value_enum, default_value_t = dump::DumpFormat::Text)]
+    format: dump::DumpFormat,
+    /// Render every video frame offscreen into DIR as text files and exit
+    #[arg(long, value_name = "DIR")]
+    headless: Option<std::path::PathBuf>,
+    /// Record the performance offscreen as an asciicast v2 FILE and exit
+    #[arg(long, value_name = "FILE")]
+    cast: Option<std::path::PathBuf>,
+    /// Audio player for the soundtrack
+    #[arg(long, value_enum, default_value_t = audio::AudioKind::Ffplay)]
+    audio: audio::AudioKind,
+    /// Player command for --audio command; {path}, {seek} and {rate} are substituted
+    #[arg(long, value_name = "TEMPLATE")]
+    audio_command: Option<String>,
+    /// Play without sound (same as --audio null)
+    #[arg(long)]
+    no_audio: bool,
+    /// Decode --video once into FILE for playback without ffmpeg, then exit
+    #[arg(long, value_name = "FILE")]
+    prerender: Option<std::path::PathBuf>,
+    /// Time FRAMES frames of --video through the render path in every render mode and exit
+    #[arg(long, value_name = "FRAMES")]
+    bench: Option<usize>,
+    /// Terminal size for --headless, --cast, --prerender and --bench
+    #[arg(long, value_name = "WxH", default_value = "80x24", value_parser = parse_size)]
+    size: (u16, u16),
+    /// Frame rate for --headless, --cast and --prerender
+    #[arg(long, default_value_t = 30.0)]
+    fps: f64,
+    /// Stop --headless or --cast after this many seconds
+    #[arg(long, value_name = "SECONDS")]
+    duration: Option<f64>,
+    /// Keep colors and modifiers as ANSI escapes in headless frames
+    #[arg(long)]
+    ansi: bool,
+    /// Width of a terminal cell divided by its height, to keep the video's proportions
+    #[arg(long, value_name = "RATIO", default_value_t = video::DEFAULT_CELL_ASPECT)]
+    cell_aspect: f64,
+    /// Play the video in color, at the given depth or the one the terminal reports
+    #[arg(long, value_name = "DEPTH", num_args = 0..=1)]
+    color: Option<Option<color::ColorDepth>>,
+    /// Dithering for the video mask (`D` cycles it in video mode)
+    #[arg(long, value_enum, default_value_t = dither::Dither::None)]
+    dither: dither::Dither,
+    /// Mask theme: classic, inverted, green, amber or one from themes.json
+    #[arg(long, value_name = "NAME", default_value = "classic")]
+    theme: String,
+    /// Render mode for --headless and --cast
+    #[arg(long, value_enum, default_value_t = RenderMode::Cast)]
+    render_mode: RenderMode,
 }
 
-fn parse_demo(path: &str) -> Result<Vec<ScriptEntry>> {
-    let content = std::fs::read_to_string(path)?;
-    let mut entries = Vec::new();
+fn parse_size(s: &str) -> Result<(u16, u16)> {
+    let (w, h) = s
+        .split_once('x')
+        .ok_or_else(|| anyhow::anyhow!("Expected WIDTHxHEIGHT, got: {s}"))?;
+    Ok((w.parse()?, h.parse()?))
+}
 
-    for (line_num, line) in content.lines().enumerate() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    anyhow::ensure!(cli.cell_aspect > 0.0, "--cell-aspect must be positive");
+    i18n::init_locale(cli.lang.as_deref());
+
+    if let Some(url) = &cli.dump {
+        return dump::run(url, cli.width, cli.format);
+    }
 
-        let parts: Vec<&str> = line.splitn(2, char::is_whitespace).collect();
-        if parts.len() != 2 {
-            anyhow::bail!(
-                "Line {actual}: Expected format 'timestamp URL', got: {line}",
-                actual = line_num + 1,
-            );
+    if cli.import_bookmarks.is_some() || cli.export_bookmarks.is_some() {
+        let mut store = bookmarks::BookmarkStore::load();
+        if let Some(path) = &cli.import_bookmarks {
+            let count = store.import_netscape(&std::fs::read_to_string(path)?);
+            println!("Imported {count} bookmarks from {path}");
         }
+        if let Some(path) = &cli.export_bookmarks {
+            std::fs::write(path, store.export_netscape())?;
+            let count = store.bookmarks.len();
+            println!("Exported {count} bookmarks to {path}");
+        }
+        return Ok(());
+    }
 
-        let timestamp = parse_timestamp(parts[0]).map_err(|e| {
-            anyhow::anyhow!(
-                "Line {actual}: Failed to parse timestamp '{t}': {e}",
-                actual = line_num + 1,
-                t = parts[0],
-            )
-        })?;
-        let url = parts[1].trim().to_string();
+    let color = cli
+        .color
+        .map(|depth| depth.unwrap_or_else(color::ColorDepth::detect));
+    let items = playlist::load(&cli.video, cli.demo.as_deref())?;
+    // Offline modes work on the first video
+    let first = items[0].clone();
 
-        entries.push(ScriptEntry { timestamp, url });
+    if let Some(out) = &cli.prerender {
+        return prerender::run(&first.video, out, cli.size, cli.fps, cli.cell_aspect);
     }
 
-    entries.sort_by(|a, b| a.timestamp.partial_cmp(&b.timestamp).unwrap());
-    Ok(entries)
-}
+    if let Some(frames) = cli.bench {
+        return bench::run(&first.video, cli.size.0, cli.size.1, frames);
+    }
+
+    let themes = theme::Theme::load_all();
+    let theme_index = theme::position(&themes, &cli.theme)?;
 
-fn parse_timestamp(s: &str) -> Result<f64> {
-    // Try MM:SS.ms format first
-    if s.contains(':') {
-        let parts: Vec<&str> = s.split(':').collect();
-        if parts.len() == 2 {
-            let minutes: f64 = parts[0].parse()?;
-            let seconds: f64 = parts[1].parse()?;
-            return Ok(minutes * 60.0 + seconds);
+    if cli.headless.is_some() || cli.cast.is_some() {
+        let opts = headless::HeadlessOptions {
+            video_path: first.video,
+            start_url: cli.start_url,
+            demo: first.demo,
+            width: cli.size.0,
+            height: cli.size.1,
+            fps: cli.fps,
+            duration: cli.duration,
+            cell_aspect: cli.cell_aspect,
+            render_mode: cli.render_mode,
+            color,
+            dither: cli.dither,
+            theme: themes[theme_index].clone(),
+        };
+        if let Some(path) = &cli.cast {
+            cast::export(&opts, path)?;
+        }
+        if let Some(out_dir) = &cli.headless {
+            headless::run(&opts, out_dir, cli.ansi)?;
         }
+        return Ok(());
     }
-    // Fall back to plain seconds
-    Ok(s.parse()?)
-}
-
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    i18n::init_locale(cli.lang.as_deref());
 
-    let demo = if let Some(demo_path) = &cli.demo {
-        parse_demo(demo_path)?
+    let restore = if cli.restore {
+        let saved = session::Session::load(&cli.session)?;
+        if cli.lang.is_none() {
+            rust_i18n::set_locale(&saved.locale);
+        }
+        Some(saved)
     } else {
-        Vec::new()
+        None
     };
 
     enable_raw_mode()?;
@@ -100,13 +224,48 @@ fn main() -> Result<()> {
     let backend = ratatui::backend::CrosstermBackend::new(stdout);
     let mut terminal = ratatui::Terminal::new(backend)?;
 
-    let mut app = App::new(cli.video, cli.start_url, demo);
+    let video = video::VideoOptions {
+        audio: audio::AudioConfig {
+            kind: if cli.no_audio {
+                audio::AudioKind::Null
+            } else {
+                cli.audio
+            },
+            command: cli.audio_command,
+        },
+        cell_aspect: cli.cell_aspect,
+        color,
+    };
+    let mut app = App::new(
+        playlist::Playlist::new(items, cli.playlist_mode),
+        cli.start_url,
+        cli.session,
+        restore,
+        video,
+        app::MaskOptions {
+            dither: cli.dither,
+            themes,
+            theme_index,
+        },
+    );
 
+    // Redraw on input, background events and new video frames, not on every poll
+    let mut dirty = true;
+    let mut last_draw = Instant::now();
     loop {
-        app.handle_events();
+        dirty |= app.handle_events();
         app.check_demo_transitions();
+        app.check_ab_loop();
+        app.apply_resize();
+        app.autosave_session();
 
-        terminal.draw(|f| ui::draw(f, &app))?;
+        let new_frame = app.video_visible() && app.engine.frames.has_new();
+        if dirty || new_frame || last_draw.elapsed() >= IDLE_REDRAW {
+            terminal.draw(|f| ui::draw(f, &app))?;
+            app.record_draw();
+            dirty = false;
+            last_draw = Instant::now();
+        }
 
         if app.auto_scroll != AutoScroll::Off {
             let base_speed_ms = 100.0;
@@ -116,23 +275,31 @@ fn main() -> Result<()> {
             if app.last_scroll_tick.elapsed() >= effective_delay {
                 let h = terminal.size()?.height;
                 app.scroll_down(h);
-                app.last_scroll_tick = std::time::Instant::now();
+                app.last_scroll_tick = Instant::now();
+                dirty = true;
             }
         }
 
-        if event::poll(Duration::from_millis(10))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
+        if event::poll(POLL_INTERVAL)? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
                     let size = terminal.size()?;
                     let (h, w) = (size.height, size.width);
                     if app.on_key(key.code, key.modifiers, h, w) {
                         break;
                     }
+                    dirty = true;
+                }
+                Event::Resize(w, h) => {
+                    app.on_resize(w, h);
+                    dirty = true;
                 }
+                _ => {}
             }
         }
     }
 
+    app.save_session();
     app.stop_video();
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
@@ -0,0 +1,299 @@
+use crate::types::ScriptEntry;
+use crate::utils::parse_timestamp;
+use anyhow::{Context, Result, bail, ensure};
+use clap::ValueEnum;
+use rand::seq::SliceRandom;
+use std::fs;
+use std::path::Path;
+
+/// What happens when a video in the playlist ends.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum PlaylistMode {
+    /// Play every video once, in order
+    Once,
+    /// Repeat the current video
+    LoopOne,
+    /// Start over after the last video
+    LoopAll,
+    /// Play in a random order, reshuffled after every pass
+    Shuffle,
+}
+
+impl PlaylistMode {
+    /// The next mode for the video mode toggle.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Once => Self::LoopOne,
+            Self::LoopOne => Self::LoopAll,
+            Self::LoopAll => Self::Shuffle,
+            Self::Shuffle => Self::Once,
+        }
+    }
+}
+
+/// One video and the demo script shown with it.
+#[derive(Clone, Debug)]
+pub struct PlaylistItem {
+    pub video: String,
+    pub demo: Vec<ScriptEntry>,
+}
+
+pub fn parse_demo(path: &str) -> Result<Vec<ScriptEntry>> {
+    let content = fs::read_to_string(path)?;
+    let mut entries = Vec::new();
+
+    for (line_num, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.splitn(2, char::is_whitespace).collect();
+        if parts.len() != 2 {
+            bail!(
+                "Line {actual}: Expected format 'timestamp URL', got: {line}",
+                actual = line_num + 1,
+            );
+        }
+
+        let timestamp = parse_timestamp(parts[0]).map_err(|e| {
+            anyhow::anyhow!(
+                "Line {actual}: Failed to parse timestamp '{t}': {e}",
+                actual = line_num + 1,
+                t = parts[0],
+            )
+        })?;
+        let url = parts[1].trim().to_string();
+
+        entries.push(ScriptEntry { timestamp, url });
+    }
+
+    entries.sort_by(|a, b| a.timestamp.partial_cmp(&b.timestamp).unwrap());
+    Ok(entries)
+}
+
+fn is_playlist_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("m3u") || e.eq_ignore_ascii_case("m3u8"))
+}
+
+/// Videos listed in an M3U file, one per line, relative to the file. `#` lines such as
+/// `#EXTINF` are skipped.
+fn read_playlist_file(path: &str) -> Result<Vec<String>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read playlist {path}"))?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if Path::new(line).is_absolute() || line.contains(':') {
+                line.to_string()
+            } else {
+                dir.join(line).to_string_lossy().into_owned()
+            }
+        })
+        .collect())
+}
+
+/// Expands the `--video` arguments into playlist items: M3U playlists (`.m3u`, `.m3u8`)
+/// are replaced by the videos they list. Every video gets `demo` when given, otherwise
+/// the `.demo` file next to it with the same name, if there is one.
+pub fn load(videos: &[String], demo: Option<&str>) -> Result<Vec<PlaylistItem>> {
+    let shared = demo.map(parse_demo).transpose()?;
+    let mut items = Vec::new();
+    for arg in videos {
+        let paths = if is_playlist_file(arg) {
+            read_playlist_file(arg)?
+        } else {
+            vec![arg.clone()]
+        };
+        for video in paths {
+            let demo = match &shared {
+                Some(demo) => demo.clone(),
+                None => {
+                    let sibling = Path::new(&video).with_extension("demo");
+                    if sibling.is_file() {
+                        let sibling = sibling.to_string_lossy();
+                        parse_demo(&sibling).with_context(|| format!("in {sibling}"))?
+                    } else {
+                        Vec::new()
+                    }
+                }
+            };
+            items.push(PlaylistItem { video, demo });
+        }
+    }
+    ensure!(!items.is_empty(), "no videos to play");
+    Ok(items)
+}
+
+/// The videos to play and the order to play them in.
+pub struct Playlist {
+    items: Vec<PlaylistItem>,
+    mode: PlaylistMode,
+    /// Indices into `items` in play order, shuffled in shuffle mode
+    order: Vec<usize>,
+    /// Position of the current item in `order`
+    position: usize,
+}
+
+impl Playlist {
+    /// Panics if `items` is empty; `load` never returns an empty list.
+    pub fn new(items: Vec<PlaylistItem>, mode: PlaylistMode) -> Self {
+        assert!(!items.is_empty(), "empty playlist");
+        let mut playlist = Self {
+            order: (0..items.len()).collect(),
+            items,
+            mode: PlaylistMode::Once,
+            position: 0,
+        };
+        playlist.set_mode(mode);
+        playlist
+    }
+
+    pub fn items(&self) -> &[PlaylistItem] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn mode(&self) -> PlaylistMode {
+        self.mode
+    }
+
+    pub fn current(&self) -> &PlaylistItem {
+        &self.items[self.order[self.position]]
+    }
+
+    /// Index of the current item in the order the videos were given.
+    pub fn current_index(&self) -> usize {
+        self.order[self.position]
+    }
+
+    /// Switches modes, keeping the current item. Shuffle plays the rest in a new order.
+    pub fn set_mode(&mut self, mode: PlaylistMode) {
+        let current = self.current_index();
+        self.mode = mode;
+        self.order = (0..self.items.len()).collect();
+        self.position = current;
+        if mode == PlaylistMode::Shuffle {
+            self.order.shuffle(&mut rand::rng());
+            let at = self.order.iter().position(|&i| i == current).unwrap();
+            self.order.swap(0, at);
+            self.position = 0;
+        }
+    }
+
+    /// Moves to what plays after the current video ends, or returns `false` and goes back
+    /// to the first video when the playlist is over.
+    pub fn advance(&mut self) -> bool {
+        if self.mode == PlaylistMode::LoopOne || self.skip(true) {
+            return true;
+        }
+        self.position = 0;
+        false
+    }
+
+    /// Moves to the next or previous video, wrapping around unless the mode is `Once`.
+    pub fn skip(&mut self, forward: bool) -> bool {
+        let len = self.order.len();
+        match (forward, self.position) {
+            (true, p) if p + 1 < len => self.position += 1,
+            (false, p) if p > 0 => self.position -= 1,
+            _ if self.mode == PlaylistMode::Once => return false,
+            (true, _) if self.mode == PlaylistMode::Shuffle => self.reshuffle(),
+            (true, _) => self.position = 0,
+            (false, _) => self.position = len - 1,
+        }
+        true
+    }
+
+    /// Starts a new shuffled pass that doesn't open with the video that just played.
+    fn reshuffle(&mut self) {
+        let last = self.current_index();
+        self.order.shuffle(&mut rand::rng());
+        if self.order.len() > 1 && self.order[0] == last {
+            let end = self.order.len() - 1;
+            self.order.swap(0, end);
+        }
+        self.position = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(n: usize) -> Vec<PlaylistItem> {
+        (0..n)
+            .map(|i| PlaylistItem {
+                video: format!("synthetic:circle:{i}"),
+                demo: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn next_videos(playlist: &mut Playlist, count: usize) -> Vec<Option<usize>> {
+        (0..count)
+            .map(|_| playlist.advance().then(|| playlist.current_index()))
+            .collect()
+    }
+
+    #[test]
+    fn advances_according_to_mode() {
+        let mut once = Playlist::new(items(3), PlaylistMode::Once);
+        assert_eq!(next_videos(&mut once, 3), [Some(1), Some(2), None]);
+        assert_eq!(once.current_index(), 0);
+
+        let mut one = Playlist::new(items(3), PlaylistMode::LoopOne);
+        assert_eq!(next_videos(&mut one, 2), [Some(0), Some(0)]);
+        // Skipping still moves on, and wraps around
+        assert!(one.skip(false));
+        assert_eq!(one.current_index(), 2);
+
+        let mut all = Playlist::new(items(3), PlaylistMode::LoopAll);
+        assert_eq!(
+            next_videos(&mut all, 4),
+            [Some(1), Some(2), Some(0), Some(1)]
+        );
+
+        // Every pass plays each video once, never the same one twice in a row
+        let mut shuffle = Playlist::new(items(4), PlaylistMode::Shuffle);
+        let mut played = vec![shuffle.current_index()];
+        played.extend(next_videos(&mut shuffle, 11).into_iter().flatten());
+        for pass in played.chunks(4) {
+            let mut pass = pass.to_vec();
+            pass.sort();
+            assert_eq!(pass, [0, 1, 2, 3]);
+        }
+        assert!(played.windows(2).all(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn loads_m3u_playlists_with_sibling_demos() {
+        let dir = std::env::temp_dir().join(format!("bad-browser-playlist-{}", std::process::id()));
+        fs::create_dir_all(dir.join("clips")).unwrap();
+        fs::write(
+            dir.join("list.m3u"),
+            "#EXTM3U\n#EXTINF:10,Intro\nclips/intro.mp4\n\nsynthetic:flash:2\n",
+        )
+        .unwrap();
+        fs::write(dir.join("clips/intro.demo"), "0:05 https://example.com\n").unwrap();
+
+        let list = dir.join("list.m3u").to_string_lossy().into_owned();
+        let loaded = load(&[list, "other.mp4".to_string()], None).unwrap();
+        let videos: Vec<_> = loaded.iter().map(|i| i.video.as_str()).collect();
+        let intro = dir.join("clips/intro.mp4").to_string_lossy().into_owned();
+        assert_eq!(videos, [intro.as_str(), "synthetic:flash:2", "other.mp4"]);
+        assert_eq!(loaded[0].demo.len(), 1);
+        assert_eq!(loaded[0].demo[0].timestamp, 5.0);
+        assert!(loaded[1].demo.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
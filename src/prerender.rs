@@ -0,0 +1,259 @@
+use crate::source::{FixedRate, SourceSpec};
+use crate::video::fit_frame;
+use anyhow::{Context, Result, bail, ensure};
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"BADRLE01";
+/// Magic, width (u16), height (u16), fps (f64), frame count (u32).
+const HEADER_LEN: usize = 8 + 2 + 2 + 8 + 4;
+
+/// A video decoded ahead of time into run-length-encoded gray frames.
+///
+/// Layout: the header, then per frame a little-endian `u32` byte length followed by
+/// `(value, run)` pairs where `run` is a LEB128 varint. Frame `n` is presented at `n / fps`.
+pub struct Prerendered {
+    pub width: usize,
+    pub height: usize,
+    pub fps: f64,
+    data: Vec<u8>,
+    frames: Vec<Range<usize>>,
+}
+
+impl Prerendered {
+    pub fn open(path: &str) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("failed to read {path}"))?;
+        Self::parse(data)
+    }
+
+    fn parse(data: Vec<u8>) -> Result<Self> {
+        ensure!(
+            data.len() >= HEADER_LEN && &data[..8] == MAGIC,
+            "not a prerendered video"
+        );
+        let width = u16::from_le_bytes([data[8], data[9]]) as usize;
+        let height = u16::from_le_bytes([data[10], data[11]]) as usize;
+        let fps = f64::from_le_bytes(data[12..20].try_into()?);
+        let count = u32::from_le_bytes(data[20..24].try_into()?) as usize;
+        ensure!(fps > 0.0, "invalid frame rate {fps}");
+
+        let mut frames = Vec::with_capacity(count);
+        let mut pos = HEADER_LEN;
+        for _ in 0..count {
+            let len_bytes = data.get(pos..pos + 4).context("truncated frame index")?;
+            let len = u32::from_le_bytes(len_bytes.try_into()?) as usize;
+            pos += 4;
+            ensure!(pos + len <= data.len(), "truncated frame data");
+            frames.push(pos..pos + len);
+            pos += len;
+        }
+
+        Ok(Self {
+            width,
+            height,
+            fps,
+            data,
+            frames,
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.frames.len() as f64 / self.fps
+    }
+
+    /// Decodes frame `index` into `out`, which must hold `width * height` bytes.
+    pub fn decode(&self, index: usize, out: &mut [u8]) -> Result<()> {
+        let range = self.frames.get(index).context("frame out of range")?;
+        decode_frame(&self.data[range.clone()], out)
+    }
+}
+
+/// Cheap check used to pick between ffmpeg and the prerendered player.
+pub fn is_prerendered(path: &str) -> bool {
+    let mut magic = [0u8; 8];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok()
+        && &magic == MAGIC
+}
+
+pub fn encode_frame(pixels: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < pixels.len() {
+        let value = pixels[i];
+        let run = pixels[i..].iter().take_while(|&&p| p == value).count();
+        out.push(value);
+        let mut n = run;
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                out.push(byte);
+                break;
+            }
+            out.push(byte | 0x80);
+        }
+        i += run;
+    }
+}
+
+pub fn decode_frame(data: &[u8], out: &mut [u8]) -> Result<()> {
+    let mut pos = 0;
+    let mut filled = 0usize;
+    while pos < data.len() {
+        let value = data[pos];
+        pos += 1;
+        let mut run = 0usize;
+        let mut shift = 0;
+        loop {
+            ensure!(shift < usize::BITS, "run length too long");
+            let byte = *data.get(pos).context("truncated run length")?;
+            pos += 1;
+            run |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let end = filled.saturating_add(run);
+        ensure!(end <= out.len(), "frame larger than {} pixels", out.len());
+        out[filled..end].fill(value);
+        filled = end;
+    }
+    if filled != out.len() {
+        bail!("frame has {filled} of {} pixels", out.len());
+    }
+    Ok(())
+}
+
+/// Streams frames into a prerender file, patching the frame count in on `finish`.
+pub struct PrerenderWriter<W: Write + Seek> {
+    out: W,
+    count: u32,
+    scratch: Vec<u8>,
+}
+
+impl<W: Write + Seek> PrerenderWriter<W> {
+    pub fn new(mut out: W, width: u16, height: u16, fps: f64) -> Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        out.write_all(&fps.to_le_bytes())?;
+        out.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            out,
+            count: 0,
+            scratch: Vec::new(),
+        })
+    }
+
+    pub fn push(&mut self, pixels: &[u8]) -> Result<()> {
+        self.scratch.clear();
+        encode_frame(pixels, &mut self.scratch);
+        self.out
+            .write_all(&(self.scratch.len() as u32).to_le_bytes())?;
+        self.out.write_all(&self.scratch)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.out.seek(SeekFrom::Start(HEADER_LEN as u64 - 4))?;
+        self.out.write_all(&self.count.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Decodes `video` once at `fps`, fitted into `width x height` cells with its proportions
+/// kept, and writes it to `out` for playback without ffmpeg.
+pub fn run(
+    video: &str,
+    out: &Path,
+    (width, height): (u16, u16),
+    fps: f64,
+    cell_aspect: f64,
+) -> Result<()> {
+    let spec = SourceSpec::parse(video)?;
+    let term = (width as usize, height as usize);
+    let (w, h) = spec
+        .native_size()
+        .map_or(term, |size| fit_frame(term, size, cell_aspect));
+    let mut frames = FixedRate::new(spec.open(w, h, 0.0, 1.0, false)?, fps)?;
+    let (w, h) = frames.size();
+
+    let mut writer = PrerenderWriter::new(
+        BufWriter::new(File::create(out)?),
+        w.try_into()?,
+        h.try_into()?,
+        fps,
+    )?;
+    while let Some(pixels) = frames.next_frame()? {
+        writer.push(pixels)?;
+    }
+    let count = writer.count;
+    writer.finish()?;
+
+    ensure!(count > 0, "{video} has no frames");
+    let size = fs::metadata(out)?.len();
+    println!(
+        "Prerendered {count} frames ({:.1}s) to {} ({size} bytes)",
+        count as f64 / fps,
+        out.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn frames_round_trip_through_file_format() {
+        let black = vec![0u8; 200];
+        let mut split = vec![0u8; 200];
+        split[70..].fill(255);
+        split[150] = 128;
+
+        let mut writer = PrerenderWriter::new(Cursor::new(Vec::new()), 20, 10, 30.0).unwrap();
+        writer.push(&black).unwrap();
+        writer.push(&split).unwrap();
+        let video = Prerendered::parse(writer.finish().unwrap().into_inner()).unwrap();
+
+        assert_eq!(
+            (video.width, video.height, video.frame_count()),
+            (20, 10, 2)
+        );
+        assert_eq!(video.duration(), 2.0 / 30.0);
+
+        let mut out = vec![1u8; 200];
+        video.decode(0, &mut out).unwrap();
+        assert_eq!(out, black);
+        video.decode(1, &mut out).unwrap();
+        assert_eq!(out, split);
+    }
+
+    #[test]
+    fn rejects_run_lengths_longer_than_usize() {
+        let mut data = vec![7u8];
+        data.extend([0x80; 10]);
+        data.push(0x01);
+        let err = decode_frame(&data, &mut [0u8; 4]).unwrap_err();
+        assert_eq!(err.to_string(), "run length too long");
+    }
+
+    #[test]
+    fn long_runs_use_varint_lengths() {
+        let mut encoded = Vec::new();
+        encode_frame(&[7u8; 300], &mut encoded);
+        assert_eq!(encoded, [7, 0xac, 0x02]);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::types::{AutoScroll, RenderMode};
+use crate::utils::data_dir;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+pub const DEFAULT_SESSION: &str = "default";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BufferState {
+    pub url: String,
+    pub history: Vec<String>,
+    pub history_index: usize,
+    pub scroll_y: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Session {
+    pub buffers: Vec<BufferState>,
+    pub active_buffer: usize,
+    pub render_mode: RenderMode,
+    pub locale: String,
+    pub auto_scroll: AutoScroll,
+    pub scroll_speed_multiplier: f32,
+}
+
+impl Session {
+    fn path(name: &str) -> PathBuf {
+        data_dir().join("sessions").join(format!("{name}.json"))
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Self::path(name);
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("No saved session at {}", path.display()))?;
+        let session: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Corrupt session file {}", path.display()))?;
+        anyhow::ensure!(!session.buffers.is_empty(), "Session {name} has no buffers");
+        Ok(session)
+    }
+
+    /// Writes through a temporary file so a crash mid-save never truncates the last good session.
+    pub fn save(&self, name: &str) -> Result<()> {
+        let path = Self::path(name);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,822 @@
+use crate::images::parse_pgm;
+use crate::prerender::{Prerendered, is_prerendered};
+use crate::utils::log_msg;
+use crate::video::{VideoEngine, spawn_decoder};
+use anyhow::{Context, Result, bail, ensure};
+use std::f64::consts::TAU;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::Arc;
+
+/// Frame rate for sources that carry no timing of their own.
+const DEFAULT_FPS: f64 = 30.0;
+const DEFAULT_SYNTHETIC_SECONDS: f64 = 10.0;
+
+/// Anything that yields 8-bit gray (or rgb24) frames for `VideoEngine`.
+pub trait FrameSource: Send {
+    /// Dimensions of the frames written by `next_frame`.
+    fn size(&self) -> (usize, usize);
+
+    /// Bytes per pixel: 1 for gray, 3 for rgb24.
+    fn channels(&self) -> usize {
+        1
+    }
+
+    /// Nominal frame rate, used to decide when a frame is too late to show.
+    fn fps(&self) -> f64;
+
+    /// Fills `out` with the next frame and returns its presentation timestamp in video
+    /// seconds, or `None` at the end of the stream.
+    fn next_frame(&mut self, out: &mut [u8]) -> Result<Option<f64>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    /// Whole frame alternates black and white every second
+    Flash,
+    /// A white disc orbiting the center
+    Circle,
+    /// Horizontal black-to-white ramp that scrolls sideways
+    Gradient,
+    /// Checkerboard whose squares invert twice a second
+    Checker,
+}
+
+/// What `--video` points at, decided once from the path.
+///
+/// Besides files ffmpeg can decode, it accepts prerendered `.rle` files, `.y4m` files,
+/// `raw:WxH@FPS:PATH` for headerless gray frames, directories of PGM frames and
+/// `synthetic:PATTERN[:SECONDS]`.
+pub enum SourceSpec {
+    Ffmpeg {
+        path: String,
+        fps: f64,
+        /// Stored frame size, used to keep the picture's proportions
+        size: Option<(usize, usize)>,
+    },
+    Prerendered(Arc<Prerendered>),
+    Y4m(PathBuf),
+    Raw {
+        path: PathBuf,
+        width: usize,
+        height: usize,
+        fps: f64,
+    },
+    ImageDir(PathBuf),
+    Synthetic {
+        pattern: Pattern,
+        seconds: f64,
+    },
+}
+
+impl SourceSpec {
+    pub fn ffmpeg(path: &str) -> Self {
+        let probe = probe_stream(path);
+        Self::Ffmpeg {
+            path: path.to_string(),
+            fps: probe.fps.unwrap_or(DEFAULT_FPS),
+            size: probe.size,
+        }
+    }
+
+    pub fn parse(path: &str) -> Result<Self> {
+        if let Some(rest) = path.strip_prefix("synthetic:") {
+            let (name, seconds) = match rest.split_once(':') {
+                Some((name, secs)) => (name, secs.parse()?),
+                None => (rest, DEFAULT_SYNTHETIC_SECONDS),
+            };
+            let pattern = match name {
+                "flash" => Pattern::Flash,
+                "circle" => Pattern::Circle,
+                "gradient" => Pattern::Gradient,
+                "checker" => Pattern::Checker,
+                _ => bail!("unknown synthetic pattern {name}"),
+            };
+            return Ok(Self::Synthetic { pattern, seconds });
+        }
+
+        if let Some(rest) = path.strip_prefix("raw:") {
+            let (format, file) = rest
+                .split_once(':')
+                .context("expected raw:WIDTHxHEIGHT@FPS:PATH")?;
+            let (size, fps) = format.split_once('@').unwrap_or((format, "30"));
+            let (width, height) = size.split_once('x').context("expected WIDTHxHEIGHT")?;
+            let (width, height, fps): (usize, usize, f64) =
+                (width.parse()?, height.parse()?, fps.parse()?);
+            ensure!(width > 0 && height > 0, "raw frames must not be empty");
+            ensure!(
+                fps.is_finite() && fps > 0.0,
+                "raw frame rate must be positive"
+            );
+            return Ok(Self::Raw {
+                path: file.into(),
+                width,
+                height,
+                fps,
+            });
+        }
+
+        let p = Path::new(path);
+        if p.is_dir() {
+            Ok(Self::ImageDir(p.into()))
+        } else if is_prerendered(path) {
+            Ok(Self::Prerendered(Arc::new(Prerendered::open(path)?)))
+        } else if p.extension().is_some_and(|e| e.eq_ignore_ascii_case("y4m")) {
+            Ok(Self::Y4m(p.into()))
+        } else {
+            Ok(Self::ffmpeg(path))
+        }
+    }
+
+    /// Only files ffmpeg decodes can have an audio track for ffplay.
+    pub fn has_audio(&self) -> bool {
+        matches!(self, Self::Ffmpeg { .. })
+    }
+
+    /// Whether `open` will decode color when asked, warning if `color` is asked of a source
+    /// that stays gray.
+    pub fn decodes_color(&self, color: bool) -> bool {
+        let ffmpeg = matches!(self, Self::Ffmpeg { .. });
+        if color && !ffmpeg {
+            log_msg(
+                "warn",
+                "--color only applies to videos ffmpeg decodes; this one stays gray",
+            );
+        }
+        color && ffmpeg
+    }
+
+    /// Pixel dimensions of the source, or `None` when it fills any size it is asked for.
+    /// Prerendered frames are already laid out in terminal cells and are shown as-is.
+    pub fn native_size(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Ffmpeg { size, .. } => *size,
+            Self::Prerendered(_) | Self::Synthetic { .. } => None,
+            Self::Y4m(path) => Y4mSource::open(path, 1, 1).map(|s| s.src),
+            Self::Raw { width, height, .. } => Some((*width, *height)),
+            Self::ImageDir(dir) => {
+                let first = list_frames(dir).ok()?.into_iter().next()?;
+                let image = parse_pgm(&fs::read(first).ok()?).ok()?;
+                Some((image.width, image.height))
+            }
+        }
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        match self {
+            Self::Ffmpeg { path, .. } => VideoEngine::get_video_duration(path),
+            Self::Prerendered(video) => Some(video.duration()),
+            Self::Y4m(path) => {
+                let source = Y4mSource::open(path, 1, 1)?;
+                let frame_bytes = (source.frame_bytes + "FRAME\n".len()) as u64;
+                let len = fs::metadata(path).ok()?.len() - source.header_len;
+                Some((len / frame_bytes) as f64 / source.fps)
+            }
+            Self::Raw {
+                path,
+                width,
+                height,
+                fps,
+            } => {
+                let len = fs::metadata(path).ok()?.len();
+                Some((len / (width * height) as u64) as f64 / fps)
+            }
+            Self::ImageDir(dir) => Some(list_frames(dir).ok()?.len() as f64 / DEFAULT_FPS),
+            Self::Synthetic { seconds, .. } => Some(*seconds),
+        }
+    }
+
+    /// Opens the stream positioned at `seek_seconds`, scaled to `width x height` where the
+    /// source allows it. ffmpeg retimes to `rate` itself; in-process sources leave it to
+    /// the player's drop/repeat logic. Only ffmpeg can decode `color`; the rest stay gray.
+    pub fn open(
+        &self,
+        width: usize,
+        height: usize,
+        seek_seconds: f64,
+        rate: f64,
+        color: bool,
+    ) -> Result<Box<dyn FrameSource>> {
+        let source: Box<dyn FrameSource> = match self {
+            Self::Ffmpeg { path, fps, .. } => Box::new(FfmpegSource::spawn(
+                path,
+                (width, height),
+                seek_seconds,
+                rate,
+                *fps,
+                color,
+            )?),
+            Self::Prerendered(video) => Box::new(PrerenderSource {
+                video: video.clone(),
+                index: (seek_seconds * video.fps) as usize,
+            }),
+            Self::Y4m(path) => {
+                let mut source = Y4mSource::open(path, width, height)
+                    .with_context(|| format!("not a readable Y4M file: {}", path.display()))?;
+                source.skip((seek_seconds * source.fps) as usize)?;
+                Box::new(source)
+            }
+            Self::Raw {
+                path,
+                width: src_w,
+                height: src_h,
+                fps,
+            } => {
+                let mut file = File::open(path)?;
+                let frame = (seek_seconds * fps) as u64;
+                file.seek(SeekFrom::Start(frame * (src_w * src_h) as u64))?;
+                Box::new(RawSource {
+                    reader: BufReader::new(file),
+                    src: (*src_w, *src_h),
+                    dst: (width, height),
+                    fps: *fps,
+                    scratch: vec![0u8; src_w * src_h],
+                    index: frame,
+                })
+            }
+            Self::ImageDir(dir) => {
+                let frames = list_frames(dir)?;
+                ensure!(!frames.is_empty(), "no .pgm frames in {}", dir.display());
+                Box::new(ImageDirSource {
+                    frames,
+                    index: (seek_seconds * DEFAULT_FPS) as usize,
+                    dst: (width, height),
+                })
+            }
+            Self::Synthetic { pattern, seconds } => Box::new(Synthetic {
+                pattern: *pattern,
+                width,
+                height,
+                frame: (seek_seconds * DEFAULT_FPS) as usize,
+                total: (seconds * DEFAULT_FPS).round() as usize,
+            }),
+        };
+        Ok(source)
+    }
+}
+
+/// Frames of a `FrameSource` on a fixed `fps` grid for the offline renderers: step `n` is
+/// the frame on screen `n / fps` seconds in, with source frames repeated or skipped as the
+/// player would.
+pub struct FixedRate {
+    source: Box<dyn FrameSource>,
+    fps: f64,
+    step: u64,
+    current: Vec<u8>,
+    /// Timestamp of `current`, `None` until the first frame is due
+    shown: Option<f64>,
+    /// The source frame after `current`, read ahead to see when it is due
+    ahead: Vec<u8>,
+    ahead_pts: Option<f64>,
+}
+
+impl FixedRate {
+    pub fn new(mut source: Box<dyn FrameSource>, fps: f64) -> Result<Self> {
+        let (w, h) = source.size();
+        let mut ahead = vec![0u8; w * h * source.channels()];
+        let ahead_pts = source.next_frame(&mut ahead)?;
+        Ok(Self {
+            source,
+            fps,
+            step: 0,
+            current: vec![0u8; ahead.len()],
+            shown: None,
+            ahead,
+            ahead_pts,
+        })
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.source.size()
+    }
+
+    /// The frame for the next step, or `None` once the last source frame has had its time.
+    pub fn next_frame(&mut self) -> Result<Option<&[u8]>> {
+        // Tolerates rounding when the source runs at the same rate
+        let now = self.step as f64 / self.fps + 1e-9;
+        while let Some(pts) = self.ahead_pts
+            && pts <= now
+        {
+            std::mem::swap(&mut self.current, &mut self.ahead);
+            self.shown = Some(pts);
+            self.ahead_pts = self.source.next_frame(&mut self.ahead)?;
+        }
+        if self.ahead_pts.is_none()
+            && self
+                .shown
+                .is_none_or(|pts| now >= pts + 1.0 / self.source.fps())
+        {
+            return Ok(None);
+        }
+        self.step += 1;
+        Ok(Some(&self.current))
+    }
+}
+
+/// ffmpeg decoding into raw gray or rgb24 on a pipe, resampled to a constant frame rate so every
+/// frame's timestamp follows from its index. At `rate` each output frame covers
+/// `rate / fps` seconds of video.
+pub struct FfmpegSource {
+    child: Child,
+    width: usize,
+    height: usize,
+    channels: usize,
+    start: f64,
+    rate: f64,
+    fps: f64,
+    index: u64,
+}
+
+impl FfmpegSource {
+    fn spawn(
+        path: &str,
+        (width, height): (usize, usize),
+        seek_seconds: f64,
+        rate: f64,
+        fps: f64,
+        color: bool,
+    ) -> Result<Self> {
+        let pix_fmt = if color { "rgb24" } else { "gray" };
+        let child = spawn_decoder(path, width, height, seek_seconds, rate, Some(fps), pix_fmt)
+            .context("failed to spawn ffmpeg")?;
+        Ok(Self {
+            child,
+            width,
+            height,
+            channels: if color { 3 } else { 1 },
+            start: seek_seconds,
+            rate,
+            fps,
+            index: 0,
+        })
+    }
+}
+
+impl FrameSource for FfmpegSource {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn fps(&self) -> f64 {
+        self.fps / self.rate
+    }
+
+    fn next_frame(&mut self, out: &mut [u8]) -> Result<Option<f64>> {
+        let stdout = self.child.stdout.as_mut().context("ffmpeg stdout closed")?;
+        if !read_frame(stdout, out)? {
+            return Ok(None);
+        }
+        let pts = self.start + self.index as f64 * self.rate / self.fps;
+        self.index += 1;
+        Ok(Some(pts))
+    }
+}
+
+impl Drop for FfmpegSource {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct PrerenderSource {
+    video: Arc<Prerendered>,
+    index: usize,
+}
+
+impl FrameSource for PrerenderSource {
+    // Frames were decoded at a fixed size; render_mask scales them to the terminal.
+    fn size(&self) -> (usize, usize) {
+        (self.video.width, self.video.height)
+    }
+
+    fn fps(&self) -> f64 {
+        self.video.fps
+    }
+
+    fn next_frame(&mut self, out: &mut [u8]) -> Result<Option<f64>> {
+        if self.index >= self.video.frame_count() {
+            return Ok(None);
+        }
+        self.video.decode(self.index, out)?;
+        self.index += 1;
+        Ok(Some((self.index - 1) as f64 / self.video.fps))
+    }
+}
+
+/// YUV4MPEG2 reader keeping only the luma plane.
+struct Y4mSource {
+    reader: BufReader<File>,
+    header_len: u64,
+    src: (usize, usize),
+    dst: (usize, usize),
+    fps: f64,
+    /// Luma plus chroma bytes per frame, excluding the `FRAME` line
+    frame_bytes: usize,
+    luma: Vec<u8>,
+    index: usize,
+}
+
+impl Y4mSource {
+    fn open(path: &Path, width: usize, height: usize) -> Option<Self> {
+        let mut reader = BufReader::new(File::open(path).ok()?);
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        let mut params = header.split_whitespace();
+        if params.next()? != "YUV4MPEG2" {
+            return None;
+        }
+
+        let (mut w, mut h, mut fps, mut colorspace) = (0usize, 0usize, DEFAULT_FPS, "420jpeg");
+        for param in params {
+            // Malformed tags end the header instead of slicing into a multibyte char
+            let (key, value) = param.split_at_checked(1)?;
+            match key {
+                "W" => w = value.parse().ok()?,
+                "H" => h = value.parse().ok()?,
+                "F" => {
+                    let (num, den) = value.split_once(':')?;
+                    fps = num.parse::<f64>().ok()? / den.parse::<f64>().ok()?;
+                }
+                "C" => colorspace = value,
+                _ => {}
+            }
+        }
+        if w == 0 || h == 0 || !(fps.is_finite() && fps > 0.0) {
+            return None;
+        }
+
+        // Only 8-bit layouts; the chroma planes are skipped
+        let (cw, ch) = (w.div_ceil(2), h.div_ceil(2));
+        let chroma = match colorspace {
+            "mono" => 0,
+            "420" | "420jpeg" | "420paldv" | "420mpeg2" => 2 * cw * ch,
+            "422" => 2 * cw * h,
+            "411" => 2 * w.div_ceil(4) * h,
+            "444" => 2 * w * h,
+            "444alpha" => 3 * w * h,
+            _ => return None,
+        };
+
+        Some(Self {
+            reader,
+            header_len: header.len() as u64,
+            src: (w, h),
+            dst: (width, height),
+            fps,
+            frame_bytes: w * h + chroma,
+            luma: vec![0u8; w * h],
+            index: 0,
+        })
+    }
+
+    /// Reads the `FRAME` line, returning `false` at end of file.
+    fn frame_header(&mut self) -> Result<bool> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(false);
+        }
+        ensure!(line.starts_with("FRAME"), "bad Y4M frame marker");
+        Ok(true)
+    }
+
+    fn skip(&mut self, frames: usize) -> Result<()> {
+        for _ in 0..frames {
+            if !self.frame_header()? {
+                break;
+            }
+            self.reader.seek_relative(self.frame_bytes as i64)?;
+            self.index += 1;
+        }
+        Ok(())
+    }
+}
+
+impl FrameSource for Y4mSource {
+    fn size(&self) -> (usize, usize) {
+        self.dst
+    }
+
+    fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    fn next_frame(&mut self, out: &mut [u8]) -> Result<Option<f64>> {
+        if !self.frame_header()? || !read_frame(&mut self.reader, &mut self.luma)? {
+            return Ok(None);
+        }
+        let chroma = (self.frame_bytes - self.luma.len()) as i64;
+        self.reader.seek_relative(chroma)?;
+        resample(&self.luma, self.src, out, self.dst);
+        self.index += 1;
+        Ok(Some((self.index - 1) as f64 / self.fps))
+    }
+}
+
+/// Headerless 8-bit gray frames, as written by `ffmpeg -f rawvideo -pix_fmt gray`.
+struct RawSource {
+    reader: BufReader<File>,
+    src: (usize, usize),
+    dst: (usize, usize),
+    fps: f64,
+    scratch: Vec<u8>,
+    index: u64,
+}
+
+impl FrameSource for RawSource {
+    fn size(&self) -> (usize, usize) {
+        self.dst
+    }
+
+    fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    fn next_frame(&mut self, out: &mut [u8]) -> Result<Option<f64>> {
+        if !read_frame(&mut self.reader, &mut self.scratch)? {
+            return Ok(None);
+        }
+        resample(&self.scratch, self.src, out, self.dst);
+        self.index += 1;
+        Ok(Some((self.index - 1) as f64 / self.fps))
+    }
+}
+
+/// A directory of binary PGM files played in name order, e.g. from
+/// `ffmpeg -i in.mp4 -pix_fmt gray dir/%05d.pgm`.
+struct ImageDirSource {
+    frames: Vec<PathBuf>,
+    index: usize,
+    dst: (usize, usize),
+}
+
+fn list_frames(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut frames: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("pgm")))
+        .collect();
+    frames.sort();
+    Ok(frames)
+}
+
+impl FrameSource for ImageDirSource {
+    fn size(&self) -> (usize, usize) {
+        self.dst
+    }
+
+    fn fps(&self) -> f64 {
+        DEFAULT_FPS
+    }
+
+    fn next_frame(&mut self, out: &mut [u8]) -> Result<Option<f64>> {
+        let Some(path) = self.frames.get(self.index) else {
+            return Ok(None);
+        };
+        let image =
+            parse_pgm(&fs::read(path)?).with_context(|| format!("bad frame {}", path.display()))?;
+        resample(&image.pixels, (image.width, image.height), out, self.dst);
+        self.index += 1;
+        Ok(Some((self.index - 1) as f64 / DEFAULT_FPS))
+    }
+}
+
+/// Procedurally generated test footage; needs no files at all.
+struct Synthetic {
+    pattern: Pattern,
+    width: usize,
+    height: usize,
+    frame: usize,
+    total: usize,
+}
+
+impl FrameSource for Synthetic {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn fps(&self) -> f64 {
+        DEFAULT_FPS
+    }
+
+    fn next_frame(&mut self, out: &mut [u8]) -> Result<Option<f64>> {
+        if self.frame >= self.total {
+            return Ok(None);
+        }
+        render_pattern(self.pattern, self.frame, self.width, self.height, out);
+        self.frame += 1;
+        Ok(Some((self.frame - 1) as f64 / DEFAULT_FPS))
+    }
+}
+
+pub fn render_pattern(pattern: Pattern, frame: usize, width: usize, height: usize, out: &mut [u8]) {
+    let t = frame as f64 / DEFAULT_FPS;
+    let (cx, cy) = (
+        width as f64 / 2.0 + (t * TAU / 4.0).cos() * width as f64 / 4.0,
+        height as f64 / 2.0 + (t * TAU / 4.0).sin() * height as f64 / 4.0,
+    );
+    let radius = width.min(height) as f64 / 4.0;
+    let square = (width.min(height) / 4).max(1);
+
+    for y in 0..height {
+        for x in 0..width {
+            out[y * width + x] = match pattern {
+                Pattern::Flash => {
+                    if (t as usize).is_multiple_of(2) {
+                        0
+                    } else {
+                        255
+                    }
+                }
+                Pattern::Circle => {
+                    let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+                    if dx * dx + dy * dy <= radius * radius {
+                        255
+                    } else {
+                        0
+                    }
+                }
+                Pattern::Gradient => (((x + frame) % width.max(1)) * 255 / width.max(1)) as u8,
+                Pattern::Checker => {
+                    let phase = (t * 2.0) as usize;
+                    if (x / square + y / square + phase).is_multiple_of(2) {
+                        255
+                    } else {
+                        0
+                    }
+                }
+            };
+        }
+    }
+}
+
+/// What ffprobe reports about the first video stream; fields it can't tell are `None`.
+#[derive(Debug, Default, PartialEq)]
+struct StreamInfo {
+    fps: Option<f64>,
+    size: Option<(usize, usize)>,
+}
+
+fn probe_stream(path: &str) -> StreamInfo {
+    Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=width,height,avg_frame_rate",
+            "-of",
+            "default=noprint_wrappers=1",
+            path,
+        ])
+        .output()
+        .ok()
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| parse_stream_info(&s))
+        .unwrap_or_default()
+}
+
+/// Parses ffprobe's `key=value` lines; the frame rate is in `30000/1001` notation.
+fn parse_stream_info(s: &str) -> StreamInfo {
+    let (mut width, mut height, mut fps) = (None, None, None);
+    for (key, value) in s.lines().filter_map(|l| l.trim().split_once('=')) {
+        match key {
+            "width" => width = value.parse::<usize>().ok().filter(|&w| w > 0),
+            "height" => height = value.parse::<usize>().ok().filter(|&h| h > 0),
+            "avg_frame_rate" => {
+                fps = value
+                    .split_once('/')
+                    .and_then(|(n, d)| Some(n.parse::<f64>().ok()? / d.parse::<f64>().ok()?))
+                    .filter(|f| f.is_finite() && *f > 0.0);
+            }
+            _ => {}
+        }
+    }
+    StreamInfo {
+        fps,
+        size: width.zip(height),
+    }
+}
+
+/// Like `read_exact`, but a clean end of stream before the first byte returns `false`.
+fn read_frame(reader: &mut impl Read, out: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(out) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Nearest-neighbour scaling of a gray frame.
+fn resample(src: &[u8], (sw, sh): (usize, usize), out: &mut [u8], (dw, dh): (usize, usize)) {
+    if (sw, sh) == (dw, dh) {
+        out.copy_from_slice(src);
+        return;
+    }
+    for y in 0..dh {
+        let sy = y * sh / dh;
+        for x in 0..dw {
+            out[y * dw + x] = src[sy * sw + x * sw / dw];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn reads_y4m_luma_and_seeks_by_frame() {
+        let path = std::env::temp_dir().join(format!("bad-browser-{}.y4m", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, "YUV4MPEG2 W2 H2 F2:1 Ip A1:1 C420jpeg").unwrap();
+        for luma in [[10, 20, 30, 40], [50, 60, 70, 80]] {
+            file.write_all(b"FRAME\n").unwrap();
+            file.write_all(&luma).unwrap();
+            file.write_all(&[128, 128]).unwrap();
+        }
+        drop(file);
+
+        let spec = SourceSpec::parse(path.to_str().unwrap()).unwrap();
+        assert_eq!(spec.duration(), Some(1.0));
+
+        let mut source = spec.open(2, 2, 0.5, 1.0, false).unwrap();
+        let mut out = [0u8; 4];
+        assert_eq!(source.next_frame(&mut out).unwrap(), Some(0.5));
+        assert_eq!(out, [50, 60, 70, 80]);
+        assert_eq!(source.next_frame(&mut out).unwrap(), None);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_malformed_y4m_headers() {
+        let path = std::env::temp_dir().join(format!("bad-browser-bad-{}.y4m", std::process::id()));
+        for header in ["YUV4MPEG2 W2 H2 éx F2:1", "YUV4MPEG2 W2 H2 F2:0"] {
+            fs::write(&path, format!("{header}\nFRAME\n\0\0\0\0\0\0")).unwrap();
+            let spec = SourceSpec::parse(path.to_str().unwrap()).unwrap();
+            assert!(spec.open(2, 2, 0.0, 1.0, false).is_err(), "{header}");
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn synthetic_sources_end_after_their_duration() {
+        let spec = SourceSpec::parse("synthetic:flash:1").unwrap();
+        assert!(!spec.has_audio());
+        assert!(!spec.decodes_color(true));
+
+        let mut source = spec.open(4, 2, 0.5, 1.0, false).unwrap();
+        let mut out = [0u8; 8];
+        let mut frames = 0;
+        while source.next_frame(&mut out).unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 15);
+    }
+
+    #[test]
+    fn fixed_rate_repeats_and_skips_frames() {
+        let spec = SourceSpec::parse("synthetic:flash:2").unwrap();
+        let count = |fps| {
+            let mut frames =
+                FixedRate::new(spec.open(1, 1, 0.0, 1.0, false).unwrap(), fps).unwrap();
+            let mut shades = Vec::new();
+            while let Some(frame) = frames.next_frame().unwrap() {
+                shades.push(frame[0]);
+            }
+            shades
+        };
+
+        // Two seconds of a source at 30 fps, either way
+        assert_eq!(count(2.0), [0, 0, 255, 255]);
+        assert_eq!(count(30.0).len(), 60);
+        assert_eq!(count(60.0).len(), 120);
+    }
+
+    #[test]
+    fn rejects_empty_or_untimed_raw_frames() {
+        assert!(SourceSpec::parse("raw:4x2@10:frames.raw").is_ok());
+        for bad in ["raw:0x2@10:f", "raw:4x0:f", "raw:4x2@0:f", "raw:4x2@inf:f"] {
+            assert!(SourceSpec::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn parses_ffprobe_stream_info() {
+        let info = parse_stream_info("width=480\nheight=360\navg_frame_rate=30000/1001\n");
+        assert_eq!(info.size, Some((480, 360)));
+        assert!((info.fps.unwrap() - 29.97).abs() < 0.01);
+
+        // Streams without a known rate report 0/0
+        let info = parse_stream_info("width=0\nheight=360\navg_frame_rate=0/0\n");
+        assert_eq!(info, StreamInfo::default());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::utils::{config_dir, log_msg};
+use anyhow::{Context, Result, bail};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::fs;
+
+const THEMES_FILE: &str = "themes.json";
+
+/// How the video mask is painted: the brightness buckets and a style for each.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    /// Highest brightness of buckets 0, 1 and 2; bucket 3 is everything brighter
+    pub cutoffs: [u8; 3],
+    /// Page text around the video
+    pub surround: Style,
+    /// Text inside the video per bucket; `None` leaves the cell blank
+    pub levels: [Option<Style>; 4],
+    /// Dots and blocks in the half-block and braille modes
+    pub pixel: Style,
+    /// Draw those dots where the picture is dark instead of bright
+    pub invert: bool,
+}
+
+impl Theme {
+    pub fn level(&self, brightness: u8) -> usize {
+        self.cutoffs
+            .iter()
+            .position(|&c| brightness <= c)
+            .unwrap_or(3)
+    }
+
+    /// Whether a half-block or braille pixel is drawn.
+    pub fn is_lit(&self, brightness: u8) -> bool {
+        (self.level(brightness) >= 2) != self.invert
+    }
+
+    /// One brightness inside each bucket, for dithering down to.
+    pub fn shades(&self) -> [u8; 4] {
+        let [a, b, c] = self.cutoffs.map(u16::from);
+        [0, ((a + 1 + b) / 2) as u8, ((b + 1 + c) / 2) as u8, 255]
+    }
+
+    pub fn classic() -> Self {
+        Self {
+            name: "classic".into(),
+            cutoffs: [30, 100, 200],
+            surround: Style::default().fg(Color::DarkGray),
+            levels: [
+                None,
+                Some(
+                    Style::default()
+                        .fg(Color::DarkGray)
+                        .bg(Color::Black)
+                        .add_modifier(Modifier::DIM),
+                ),
+                Some(Style::default().fg(Color::White).bg(Color::Black)),
+                Some(
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::White)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            ],
+            pixel: Style::default().fg(Color::White).bg(Color::Black),
+            invert: false,
+        }
+    }
+
+    /// The built-in themes, `classic` first.
+    pub fn builtin() -> Vec<Self> {
+        let classic = Self::classic();
+        let [_, dim, normal, bright] = classic.levels;
+        let inverted = Self {
+            name: "inverted".into(),
+            levels: [bright, normal, dim, None],
+            invert: true,
+            ..classic.clone()
+        };
+        vec![
+            classic,
+            inverted,
+            Self::monochrome("green", Color::Green, Color::LightGreen),
+            Self::monochrome("amber", Color::Indexed(130), Color::Indexed(214)),
+        ]
+    }
+
+    /// A single-hue CRT look: dim text around the video, glowing text inside it.
+    fn monochrome(name: &str, dim: Color, glow: Color) -> Self {
+        let on_black = Style::default().bg(Color::Black);
+        Self {
+            name: name.into(),
+            cutoffs: [30, 100, 200],
+            surround: on_black.fg(dim).add_modifier(Modifier::DIM),
+            levels: [
+                None,
+                Some(on_black.fg(dim)),
+                Some(on_black.fg(glow)),
+                Some(on_black.fg(glow).add_modifier(Modifier::BOLD)),
+            ],
+            pixel: on_black.fg(glow),
+            invert: false,
+        }
+    }
+
+    /// Built-in themes followed by the user's from `$XDG_CONFIG_HOME/bad-browser/themes.json`.
+    /// A user theme with a built-in name replaces it.
+    pub fn load_all() -> Vec<Self> {
+        let mut themes = Self::builtin();
+        let path = config_dir().join(THEMES_FILE);
+        let Ok(content) = fs::read_to_string(&path) else {
+            return themes;
+        };
+        match parse_themes(&content) {
+            Ok(user) => {
+                for theme in user {
+                    match themes.iter_mut().find(|t| t.name == theme.name) {
+                        Some(existing) => *existing = theme,
+                        None => themes.push(theme),
+                    }
+                }
+            }
+            Err(e) => log_msg("error", &format!("Themes: failed to load {path:?}: {e:#}")),
+        }
+        themes
+    }
+}
+
+/// Index of the theme called `name`.
+pub fn position(themes: &[Theme], name: &str) -> Result<usize> {
+    themes.iter().position(|t| t.name == name).with_context(|| {
+        let names: Vec<_> = themes.iter().map(|t| t.name.as_str()).collect();
+        format!(
+            "unknown theme {name:?}, expected one of {}",
+            names.join(", ")
+        )
+    })
+}
+
+/// A style as written in the config file, e.g.
+/// `{ "fg": "light green", "bg": "#000000", "modifiers": ["bold"] }`.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StyleSpec {
+    fg: Option<String>,
+    bg: Option<String>,
+    modifiers: Vec<String>,
+}
+
+impl StyleSpec {
+    fn to_style(&self) -> Result<Style> {
+        let color = |name: &str| {
+            name.parse::<Color>()
+                .map_err(|_| anyhow::anyhow!("unknown color {name:?}"))
+        };
+        let mut style = Style::default();
+        if let Some(fg) = &self.fg {
+            style = style.fg(color(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(color(bg)?);
+        }
+        for name in &self.modifiers {
+            let modifier = Modifier::from_name(&name.to_uppercase())
+                .with_context(|| format!("unknown modifier {name:?}"))?;
+            style = style.add_modifier(modifier);
+        }
+        Ok(style)
+    }
+}
+
+/// A theme in the config file. Anything left out is taken from `classic`.
+#[derive(Deserialize)]
+struct ThemeSpec {
+    name: String,
+    cutoffs: Option<[u8; 3]>,
+    surround: Option<StyleSpec>,
+    levels: Option<[Option<StyleSpec>; 4]>,
+    pixel: Option<StyleSpec>,
+    #[serde(default)]
+    invert: bool,
+}
+
+fn parse_themes(content: &str) -> Result<Vec<Theme>> {
+    let specs: Vec<ThemeSpec> = serde_json::from_str(content)?;
+    specs
+        .into_iter()
+        .map(|spec| {
+            let classic = Theme::classic();
+            let cutoffs = spec.cutoffs.unwrap_or(classic.cutoffs);
+            if !cutoffs.is_sorted() {
+                bail!("theme {}: cutoffs must be ascending", spec.name);
+            }
+            let style = |s: Option<StyleSpec>, default: Style| {
+                s.map_or(Ok(default), |s| s.to_style())
+                    .with_context(|| format!("theme {}", spec.name))
+            };
+            let levels = match spec.levels {
+                Some(levels) => {
+                    let mut out = [None; 4];
+                    for (slot, level) in out.iter_mut().zip(levels) {
+                        *slot = level
+                            .map(|l| style(Some(l), Style::default()))
+                            .transpose()?;
+                    }
+                    out
+                }
+                None => classic.levels,
+            };
+            Ok(Theme {
+                surround: style(spec.surround, classic.surround)?,
+                pixel: style(spec.pixel, classic.pixel)?,
+                name: spec.name,
+                cutoffs,
+                levels,
+                invert: spec.invert,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::brightness_level;
+
+    #[test]
+    fn classic_matches_default_buckets() {
+        let classic = Theme::classic();
+        for b in 0..=255 {
+            assert_eq!(classic.level(b), brightness_level(b));
+        }
+        assert_eq!(classic.shades(), [0, 65, 150, 255]);
+    }
+
+    #[test]
+    fn parses_user_themes_over_classic() {
+        let themes = parse_themes(
+            r##"[{
+                "name": "ice",
+                "cutoffs": [20, 90, 180],
+                "levels": [null, {"fg": "blue"}, {"fg": "light cyan"},
+                           {"fg": "#000000", "bg": "white", "modifiers": ["bold", "italic"]}]
+            }]"##,
+        )
+        .unwrap();
+        let ice = &themes[0];
+        assert_eq!(ice.level(21), 1);
+        assert_eq!(ice.surround, Theme::classic().surround);
+        assert_eq!(ice.levels[2], Some(Style::default().fg(Color::LightCyan)));
+        assert_eq!(
+            ice.levels[3],
+            Some(
+                Style::default()
+                    .fg(Color::Rgb(0, 0, 0))
+                    .bg(Color::White)
+                    .add_modifier(Modifier::BOLD | Modifier::ITALIC)
+            )
+        );
+
+        assert!(parse_themes(r#"[{"name": "bad", "surround": {"fg": "plaid"}}]"#).is_err());
+    }
+}
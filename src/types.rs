@@ -1,4 +1,14 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+pub type CachedPage = (
+    Arc<String>,
+    Arc<Vec<char>>,
+    Arc<HashMap<String, String>>,
+    Arc<Vec<String>>,
+);
 
 #[derive(Clone, Debug)]
 pub struct ScriptEntry {
@@ -11,9 +21,18 @@ pub enum AppMode {
     Normal,
     Insert,
     Video,
+    Bookmarks,
 }
 
+/// What the Insert mode input line is editing.
 #[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Prompt {
+    Url,
+    BookmarkTags,
+    GotoTime,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum AutoScroll {
     Off,
     Linear,
@@ -21,29 +40,87 @@ pub enum AutoScroll {
     Demo,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, ValueEnum)]
 pub enum RenderMode {
     Cast,
     Fit,
+    /// Whole words wrapped inside each lit run, like text flowing around a shape
+    Flow,
+    /// Two pixels per cell with `▀`/`▄`/`█`
+    HalfBlock,
+    /// 2x4 pixels per cell with braille patterns
+    Braille,
+    /// Page text only along the silhouette's contours
+    Edge,
+    /// `/`, `\`, `|` and `-` following the contours
+    Outline,
+}
+
+impl RenderMode {
+    /// The next mode in the `m` cycle.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Cast => Self::Fit,
+            Self::Fit => Self::Flow,
+            Self::Flow => Self::HalfBlock,
+            Self::HalfBlock => Self::Braille,
+            Self::Braille => Self::Edge,
+            Self::Edge => Self::Outline,
+            Self::Outline => Self::Cast,
+        }
+    }
+
+    /// Video pixels drawn per cell, across and down.
+    pub fn cell_pixels(self) -> (usize, usize) {
+        match self {
+            Self::Cast | Self::Fit | Self::Flow | Self::Edge | Self::Outline => (1, 1),
+            Self::HalfBlock => (1, 2),
+            Self::Braille => (2, 4),
+        }
+    }
+}
+
+/// An `<img>` found while parsing, replaced in the page text by `image_marker(index)`.
+#[derive(Clone, Debug)]
+pub struct ImageRef {
+    pub src: String,
+    pub alt: String,
+}
+
+pub fn image_marker(index: usize) -> String {
+    format!("[IMG {index}]")
 }
 
 #[derive(Clone, Debug)]
 pub enum BgEvent {
     PageLoaded {
+        buffer_id: usize,
         url: String,
+        title: String,
         text: String,
         links: Vec<String>,
         dense_text: Vec<char>,
         link_map: HashMap<String, String>,
+        images: Vec<ImageRef>,
         is_history_nav: bool,
     },
     PrefetchReady {
+        buffer_id: usize,
         url: String,
+        title: String,
         text: String,
         links: Vec<String>,
         dense_text: Vec<char>,
         link_map: HashMap<String, String>,
+        images: Vec<ImageRef>,
+    },
+    ImageReady {
+        buffer_id: usize,
+        page_url: String,
+        index: usize,
+        art: Vec<String>,
+        alt: String,
     },
     VideoEnded(usize),
-    Error(String),
+    Error(usize, String),
 }
@@ -1,40 +1,216 @@
 use crate::app::App;
+use crate::color::{ColorDepth, luma};
+use crate::dither::Dither;
+use crate::edge::sobel;
+use crate::flow::{cluster_width, flow_words};
 use crate::i18n::t;
+use crate::playlist::PlaylistMode;
 use crate::text::clamp_cursor;
-use crate::types::{AppMode, AutoScroll, RenderMode};
-use crate::utils::decode_url;
+use crate::theme::Theme;
+use crate::types::{AppMode, AutoScroll, Prompt, RenderMode};
+use crate::utils::{decode_url, format_timestamp};
 use ratatui::{
     Frame,
+    buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Paragraph, Wrap},
+    widgets::{Clear, List, ListItem, ListState, Paragraph, Widget, Wrap},
 };
 use unicode_width::UnicodeWidthChar;
 
 pub fn draw(f: &mut Frame, app: &App) {
+    let tab_line_height = if app.buffers.len() > 1 { 1 } else { 0 };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
+            Constraint::Length(tab_line_height),
             Constraint::Min(0),
             Constraint::Length(1),
             Constraint::Length(1),
         ])
         .split(f.area());
 
-    let area = chunks[0];
+    let area = chunks[1];
 
-    if app.mode == AppMode::Video {
+    if tab_line_height > 0 {
+        render_tab_line(f, app, chunks[0]);
+    }
+
+    if app.video_visible() {
         render_video_mask(f, app, area);
+    } else if app.mode == AppMode::Bookmarks {
+        render_bookmarks(f, app, area);
     } else {
-        let p = Paragraph::new(app.page_text.as_ref().as_str())
+        let buffer = app.buffer();
+        let p = Paragraph::new(buffer.page_text.as_ref().as_str())
             .wrap(Wrap { trim: false })
-            .scroll((app.scroll_y, 0));
+            .scroll((buffer.scroll_y, 0));
         f.render_widget(p, area);
     }
 
-    render_status_bar(f, app, chunks[1]);
-    render_hints(f, app, chunks[2]);
+    if app.mode == AppMode::Insert && !app.completions.is_empty() {
+        render_completions(f, app, area);
+    }
+    if app.debug.visible {
+        render_debug_overlay(f, app, area);
+    }
+
+    render_status_bar(f, app, chunks[2]);
+    render_hints(f, app, chunks[3]);
+}
+
+fn render_completions(f: &mut Frame, app: &App, area: Rect) {
+    let height = (app.completions.len() as u16).min(area.height);
+    let popup = Rect {
+        y: area.bottom() - height,
+        height,
+        ..area
+    };
+
+    let items: Vec<ListItem> = app
+        .completions
+        .iter()
+        .map(|(url, title)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(decode_url(url), Style::default().fg(Color::Cyan)),
+                Span::raw("  "),
+                Span::styled(title.clone(), Style::default().fg(Color::Gray)),
+            ]))
+        })
+        .collect();
+
+    let mut state = ListState::default().with_selected(Some(app.completion_selected));
+    let list = List::new(items)
+        .style(Style::default().bg(Color::Black))
+        .highlight_style(Style::default().bg(Color::DarkGray).bold());
+    f.render_widget(Clear, popup);
+    f.render_stateful_widget(list, popup, &mut state);
+}
+
+/// Playback and loading counters in the top-right corner, toggled with F3.
+fn render_debug_overlay(f: &mut Frame, app: &App, area: Rect) {
+    let stats = app.engine.clock.stats();
+    let frame_age = app
+        .engine
+        .frames
+        .front_published()
+        .map_or("-".to_string(), |t| {
+            format!("{} ms", t.elapsed().as_millis())
+        });
+    let rows = [
+        (
+            "render",
+            format!("{:.1} fps", app.debug.render.per_second()),
+        ),
+        (
+            "decode",
+            format!("{:.1} fps", app.debug.decode.per_second()),
+        ),
+        ("dropped", stats.dropped.to_string()),
+        ("A/V drift", format!("{:+.0} ms", stats.drift * 1000.0)),
+        ("fetches", app.pending_fetches().to_string()),
+        (
+            "demo cache",
+            format!("{}/{}", app.demo_cached(), app.demo.len()),
+        ),
+        ("frame age", frame_age),
+    ];
+
+    let width = 24.min(area.width);
+    let popup = Rect {
+        x: area.right() - width,
+        width,
+        height: (rows.len() as u16).min(area.height),
+        ..area
+    };
+    let lines: Vec<Line> = rows
+        .into_iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!(" {label:<11}"), Style::default().fg(Color::Gray)),
+                Span::styled(format!("{value:>11} "), Style::default().fg(Color::Yellow)),
+            ])
+        })
+        .collect();
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).bg(Color::Black), popup);
+}
+
+fn render_tab_line(f: &mut Frame, app: &App, area: Rect) {
+    const MAX_TITLE_WIDTH: usize = 24;
+
+    let mut spans = Vec::with_capacity(app.buffers.len());
+    for (i, buffer) in app.buffers.iter().enumerate() {
+        let mut title = buffer.title();
+        if title.is_empty() {
+            title = t!("labels.empty_buffer");
+        }
+        if title.chars().count() > MAX_TITLE_WIDTH {
+            title = title.chars().take(MAX_TITLE_WIDTH - 1).collect::<String>() + "…";
+        }
+        let loading = if buffer.is_loading { " ⏳" } else { "" };
+
+        let style = if i == app.active_buffer {
+            Style::default().bg(Color::Blue).fg(Color::Black).bold()
+        } else {
+            Style::default().bg(Color::DarkGray).fg(Color::Gray)
+        };
+        spans.push(Span::styled(
+            format!(" {n}: {title}{loading} ", n = i + 1),
+            style,
+        ));
+        spans.push(Span::raw(" "));
+    }
+
+    f.render_widget(Paragraph::new(Line::from(spans)).bg(Color::Black), area);
+}
+
+fn render_bookmarks(f: &mut Frame, app: &App, area: Rect) {
+    let store = &app.bookmarks;
+    if store.bookmarks.is_empty() {
+        f.render_widget(
+            Paragraph::new(t!("ui.no_bookmarks")).fg(Color::DarkGray),
+            area,
+        );
+        return;
+    }
+
+    let items: Vec<ListItem> = store
+        .bookmarks
+        .iter()
+        .map(|b| {
+            let quickmark = store
+                .quickmarks
+                .iter()
+                .find(|(_, url)| **url == b.url)
+                .map(|(key, _)| format!("'{key} "))
+                .unwrap_or_else(|| "   ".to_string());
+            let added = chrono::DateTime::from_timestamp(b.added, 0)
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+
+            let mut spans = vec![
+                Span::styled(quickmark, Style::default().fg(Color::Magenta)),
+                Span::styled(added, Style::default().fg(Color::DarkGray)),
+                Span::raw(" "),
+                Span::styled(b.title.clone(), Style::default().bold()),
+                Span::raw(" "),
+                Span::styled(decode_url(&b.url), Style::default().fg(Color::Cyan)),
+            ];
+            for tag in &b.tags {
+                spans.push(Span::styled(
+                    format!(" #{tag}"),
+                    Style::default().fg(Color::Green),
+                ));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let mut state = ListState::default().with_selected(Some(app.bookmark_selected));
+    let list = List::new(items).highlight_style(Style::default().bg(Color::DarkGray));
+    f.render_stateful_widget(list, area, &mut state);
 }
 
 fn render_status_bar(f: &mut Frame, app: &App, area: Rect) {
@@ -52,8 +228,9 @@ fn render_status_bar(f: &mut Frame, app: &App, area: Rect) {
             }
         }
         AppMode::Insert => (Color::Yellow, format!(" {} ", t!("status.insert"))),
+        AppMode::Bookmarks => (Color::Green, format!(" {} ", t!("status.bookmarks"))),
         AppMode::Video => {
-            if app.engine.is_paused {
+            if app.engine.clock.is_paused() {
                 (Color::Gray, format!(" {} ", t!("status.pause")))
             } else {
                 (Color::Red, format!(" {} ", t!("status.video")))
@@ -71,19 +248,40 @@ fn render_status_bar(f: &mut Frame, app: &App, area: Rect) {
             t!("status.goto_prefix", hint = app.hint_buffer),
             Style::default().fg(Color::Yellow).bold(),
         ));
+    } else if app.pending_mark.is_some() {
+        left_spans.push(Span::styled(
+            t!("status.mark_prefix"),
+            Style::default().fg(Color::Yellow).bold(),
+        ));
     } else if app.mode == AppMode::Insert {
-        let nice_input = decode_url(&app.url_input);
-        let safe_cursor = clamp_cursor(&nice_input, app.cursor_pos);
+        let (nice_input, cursor) = match app.prompt {
+            Prompt::Url => (decode_url(&app.buffer().url_input), app.buffer().cursor_pos),
+            Prompt::BookmarkTags => {
+                left_spans.push(Span::styled(
+                    t!("status.tags_prefix"),
+                    Style::default().fg(Color::Yellow).bold(),
+                ));
+                (app.prompt_input.clone(), app.prompt_cursor)
+            }
+            Prompt::GotoTime => {
+                left_spans.push(Span::styled(
+                    t!("status.time_prefix"),
+                    Style::default().fg(Color::Yellow).bold(),
+                ));
+                (app.prompt_input.clone(), app.prompt_cursor)
+            }
+        };
+        let safe_cursor = clamp_cursor(&nice_input, cursor);
         let (l, r) = nice_input.split_at(safe_cursor);
 
         left_spans.push(Span::raw(l.to_string()));
         left_spans.push(Span::styled("█", Style::default().fg(Color::White)));
         left_spans.push(Span::raw(r.to_string()));
     } else {
-        left_spans.push(Span::raw(decode_url(&app.url_input)));
+        left_spans.push(Span::raw(decode_url(&app.buffer().url_input)));
     }
 
-    if app.is_loading {
+    if app.buffer().is_loading {
         left_spans.push(Span::raw(" "));
         left_spans.push(Span::styled(
             "⏳",
@@ -117,11 +315,7 @@ fn render_status_bar(f: &mut Frame, app: &App, area: Rect) {
     let mut right_spans = Vec::new();
 
     if app.mode == AppMode::Video {
-        let current = if app.engine.is_paused {
-            app.engine.seek_time
-        } else {
-            app.engine.seek_time + app.engine.start_instant.elapsed().as_secs_f64()
-        };
+        let current = app.engine.clock.position();
 
         let total = app.engine.duration;
 
@@ -147,6 +341,43 @@ fn render_status_bar(f: &mut Frame, app: &App, area: Rect) {
             (total as u64) % 60
         );
         right_spans.push(Span::styled(time_str, Style::default().fg(Color::Cyan)));
+
+        if let Some(a) = app.loop_a {
+            let b = app.loop_b.map(format_timestamp).unwrap_or_default();
+            right_spans.push(Span::styled(
+                format!("[A {}-B {b}] ", format_timestamp(a)),
+                Style::default().fg(Color::Magenta),
+            ));
+        }
+
+        let rate = app.engine.clock.rate();
+        let rate_color = if rate == 1.0 {
+            Color::Gray
+        } else {
+            Color::LightYellow
+        };
+        right_spans.push(Span::styled(
+            format!("{rate}x "),
+            Style::default().fg(rate_color).bold(),
+        ));
+
+        let playlist = &app.playlist;
+        let mode_txt = match playlist.mode() {
+            PlaylistMode::Once => "",
+            PlaylistMode::LoopOne => " ONE",
+            PlaylistMode::LoopAll => " ALL",
+            PlaylistMode::Shuffle => " SHF",
+        };
+        if playlist.len() > 1 || !mode_txt.is_empty() {
+            right_spans.push(Span::styled(
+                format!(
+                    "[{}/{}{mode_txt}] ",
+                    playlist.current_index() + 1,
+                    playlist.len()
+                ),
+                Style::default().fg(Color::LightBlue),
+            ));
+        }
     }
 
     if app.mode == AppMode::Video && !app.demo.is_empty() {
@@ -164,6 +395,17 @@ fn render_status_bar(f: &mut Frame, app: &App, area: Rect) {
     let render_txt = match app.render_mode {
         RenderMode::Cast => "[CST]",
         RenderMode::Fit => "[FIT]",
+        RenderMode::Flow => "[FLW]",
+        RenderMode::HalfBlock => "[HLF]",
+        RenderMode::Braille => "[BRL]",
+        RenderMode::Edge => "[EDG]",
+        RenderMode::Outline => "[OUT]",
+    };
+    let dither_txt = match app.dither {
+        Dither::None => "",
+        Dither::Bayer => "[BYR]",
+        Dither::FloydSteinberg => "[F-S]",
+        Dither::BlueNoise => "[BLU]",
     };
 
     right_spans.push(Span::styled(
@@ -176,6 +418,11 @@ fn render_status_bar(f: &mut Frame, app: &App, area: Rect) {
     } else {
         Style::default().fg(Color::Gray)
     };
+    if app.theme_index != 0 {
+        let theme_txt = format!("[{}]", app.theme().name);
+        right_spans.push(Span::styled(theme_txt, render_style));
+    }
+    right_spans.push(Span::styled(dither_txt, render_style));
     right_spans.push(Span::styled(render_txt, render_style));
 
     f.render_widget(
@@ -195,6 +442,7 @@ fn render_hints(f: &mut Frame, app: &App, area: Rect) {
     let hints = match app.mode {
         AppMode::Insert => t!("hints.insert"),
         AppMode::Video => t!("hints.video"),
+        AppMode::Bookmarks => t!("hints.bookmarks"),
         _ => {
             if app.hint_mode_active {
                 t!("hints.link_typing")
@@ -221,108 +469,366 @@ fn render_hints(f: &mut Frame, app: &App, area: Rect) {
     }
 }
 
+/// Buckets a gray pixel into the four shades the mask (and inline image art) distinguish.
+pub fn brightness_level(brightness: u8) -> usize {
+    match brightness {
+        0..=30 => 0,
+        31..=100 => 1,
+        101..=200 => 2,
+        201..=255 => 3,
+    }
+}
+
+/// Shades `render_mask` tells apart in `render_mode`, for dithering down to: one inside
+/// each of the theme's brightness buckets, or just off and on for the sub-cell modes.
+/// The contour modes need the smooth frame, so they are not dithered.
+pub fn mask_levels(theme: &Theme, render_mode: RenderMode) -> Option<Vec<u8>> {
+    match render_mode {
+        RenderMode::Cast | RenderMode::Fit | RenderMode::Flow => Some(theme.shades().to_vec()),
+        RenderMode::HalfBlock | RenderMode::Braille => Some(vec![0, 255]),
+        RenderMode::Edge | RenderMode::Outline => None,
+    }
+}
+
 fn render_video_mask(f: &mut Frame, app: &App, area: Rect) {
-    let (buf, src_w, src_h) = {
-        let b = app.engine.buffer.lock().unwrap();
-        let w = *app.engine.source_width.lock().unwrap();
-        let h = *app.engine.source_height.lock().unwrap();
-        if b.len() == 0 {
-            f.render_widget(Paragraph::new(t!("ui.buffering")), area);
-            return;
+    let mut latest = app.engine.frames.latest();
+    let dense_text = &app.buffer().dense_text;
+    if latest.pixels.is_empty() || dense_text.is_empty() {
+        f.render_widget(Paragraph::new(t!("ui.buffering")), area);
+        return;
+    }
+    let (width, height, cell_pixels) = (latest.width, latest.height, latest.cell_pixels);
+
+    // Sources ffmpeg doesn't decode stay gray in color mode
+    let color = app
+        .engine
+        .color
+        .filter(|_| latest.pixels.len() == width * height * 3);
+    let theme = app.theme();
+    let levels = mask_levels(theme, app.render_mode)
+        .filter(|_| color.is_none() && app.dither != Dither::None);
+    let pixels = match &levels {
+        Some(levels) => latest.dithered(app.dither, levels),
+        None => &latest.pixels,
+    };
+    let frame = MaskFrame {
+        pixels,
+        width,
+        height,
+        cell_pixels,
+        color,
+    };
+    f.render_widget(
+        VideoMask {
+            frame: &frame,
+            dense_text,
+            scroll_y: app.buffer().scroll_y,
+            render_mode: app.render_mode,
+            theme,
+        },
+        area,
+    );
+}
+
+/// One decoded frame as handed to `render_mask`.
+pub struct MaskFrame<'a> {
+    pub pixels: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+    /// Pixels per terminal cell the frame was decoded at, across and down
+    pub cell_pixels: (usize, usize),
+    /// `Some` when `pixels` is rgb24, to be shown at that depth; gray otherwise
+    pub color: Option<ColorDepth>,
+}
+
+impl MaskFrame<'_> {
+    /// Brightness of every pixel, row by row.
+    fn gray(&self) -> Vec<u8> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| self.pixel(x, y).0))
+            .collect()
+    }
+
+    /// Brightness and, for color frames, the color of pixel `(x, y)`.
+    fn pixel(&self, x: usize, y: usize) -> (u8, Option<Color>) {
+        let index = y * self.width + x;
+        match self.color {
+            Some(depth) => {
+                let i = (index * 3).min(self.pixels.len() - 3);
+                let rgb = [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]];
+                (luma(rgb), Some(depth.quantize(rgb)))
+            }
+            None => (self.pixels[index.min(self.pixels.len() - 1)], None),
         }
-        (b.clone(), w, h)
+    }
+}
+
+/// Braille dot bits by pixel column and row within the cell.
+const BRAILLE_DOTS: [(usize, usize, u32); 8] = [
+    (0, 0, 0),
+    (0, 1, 1),
+    (0, 2, 2),
+    (1, 0, 3),
+    (1, 1, 4),
+    (1, 2, 5),
+    (0, 3, 6),
+    (1, 3, 7),
+];
+
+/// Glyph and style for video cell `(cx, cy)` of a `draw_w x draw_h` cell picture in the
+/// half-block and braille modes, sampled at the mode's pixels per cell. `None` is a blank.
+fn sub_cell(
+    frame: &MaskFrame,
+    render_mode: RenderMode,
+    theme: &Theme,
+    (cx, cy): (usize, usize),
+    (draw_w, draw_h): (usize, usize),
+) -> Option<(char, Style)> {
+    let (nx, ny) = render_mode.cell_pixels();
+    let sample = |i: usize, j: usize| {
+        let sx = ((cx * nx + i) * frame.width / (draw_w * nx)).min(frame.width - 1);
+        let sy = ((cy * ny + j) * frame.height / (draw_h * ny)).min(frame.height - 1);
+        frame.pixel(sx, sy)
     };
+    let lit = |brightness: u8| theme.is_lit(brightness);
+
+    if render_mode == RenderMode::Braille {
+        let mut bits = 0;
+        let mut brightest = (0, None);
+        for (i, j, bit) in BRAILLE_DOTS {
+            let (brightness, color) = sample(i, j);
+            if lit(brightness) {
+                bits |= 1 << bit;
+                if brightness >= brightest.0 {
+                    brightest = (brightness, color);
+                }
+            }
+        }
+        let ch = char::from_u32(0x2800 + bits).filter(|_| bits != 0)?;
+        return Some(match brightest.1 {
+            Some(color) => (ch, Style::default().fg(color)),
+            None => (ch, theme.pixel),
+        });
+    }
 
-    if app.dense_text.len() == 0 {
-        f.render_widget(Paragraph::new(t!("ui.buffering")), area);
+    let ((top, top_color), (bottom, bottom_color)) = (sample(0, 0), sample(0, 1));
+    if let (Some(fg), Some(bg)) = (top_color, bottom_color) {
+        return Some(('▀', Style::default().fg(fg).bg(bg)));
+    }
+    let glyph = match (lit(top), lit(bottom)) {
+        (false, false) => return None,
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (true, true) => '█',
+    };
+    Some((glyph, theme.pixel))
+}
+
+/// Writes `ch` at `(x, y)` of `area`, or a blank for `None`, and clears the cells a wide
+/// char covers the way `Buffer::set_string` does.
+fn put(buf: &mut Buffer, area: Rect, (x, y): (usize, usize), cell: Option<(char, Style)>) {
+    let (x, y) = (area.x + x as u16, area.y + y as u16);
+    let Some((ch, style)) = cell else {
+        buf[(x, y)].set_char(' ');
         return;
+    };
+    buf[(x, y)].set_char(ch).set_style(style);
+    for i in 1..UnicodeWidthChar::width(ch).unwrap_or(1) as u16 {
+        buf[(x + i, y)].reset();
     }
+}
+
+/// `dense_text` masked by a frame's silhouette, written straight into the buffer without
+/// building spans. Shared by the live UI, headless rendering and `--bench`.
+pub struct VideoMask<'a> {
+    pub frame: &'a MaskFrame<'a>,
+    pub dense_text: &'a [char],
+    pub scroll_y: u16,
+    pub render_mode: RenderMode,
+    pub theme: &'a Theme,
+}
 
-    let term_w = area.width as usize;
-    let term_h = area.height as usize;
+impl Widget for VideoMask<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let Self {
+            frame,
+            dense_text,
+            scroll_y,
+            render_mode,
+            theme,
+        } = self;
+        let (src_w, src_h) = (frame.width, frame.height);
+        if frame.pixels.is_empty() || dense_text.is_empty() || src_w == 0 || src_h == 0 {
+            return;
+        }
+        let term_w = area.width as usize;
+        let term_h = area.height as usize;
+
+        // Frame size in cells
+        let cells_w = src_w as f64 / frame.cell_pixels.0 as f64;
+        let cells_h = src_h as f64 / frame.cell_pixels.1 as f64;
+        let scale_w = term_w as f64 / cells_w;
+        let scale_h = term_h as f64 / cells_h;
+        let scale = scale_w.min(scale_h);
+
+        let draw_w = (cells_w * scale) as usize;
+        let draw_h = (cells_h * scale) as usize;
+
+        let off_x = (term_w.saturating_sub(draw_w)) / 2;
+        let off_y = (term_h.saturating_sub(draw_h)) / 2;
+
+        let gradients = matches!(render_mode, RenderMode::Edge | RenderMode::Outline)
+            .then(|| sobel(&frame.gray(), src_w, src_h));
+
+        // Text style of the video under a cell of the drawn area, `None` where the theme
+        // leaves it blank
+        let cell_style = |cx: usize, cy: usize| {
+            let sx = ((cx * src_w) / draw_w).min(src_w - 1);
+            let sy = ((cy * src_h) / draw_h).min(src_h - 1);
+            let (brightness, color) = frame.pixel(sx, sy);
+            let level = theme.level(brightness);
+            theme.levels[level].map(|style| match color {
+                // Color frames keep the theme's shape but paint with the pixel
+                Some(c) if level == 3 => Style::default()
+                    .fg(c)
+                    .bg(Color::Black)
+                    .add_modifier(Modifier::BOLD),
+                Some(c) => Style::default().fg(c).bg(Color::Black),
+                None => style,
+            })
+        };
 
-    let scale_w = term_w as f64 / src_w as f64;
-    let scale_h = term_h as f64 / src_h as f64;
-    let scale = scale_w.min(scale_h);
+        // Flow lays out whole words per row, so it needs every lit cell up front to know the
+        // widest run a word can ever get. Like Fit, it writes wherever the theme has a style.
+        let flow_cells: Option<Vec<Vec<Option<Style>>>> =
+            (render_mode == RenderMode::Flow).then(|| {
+                (0..draw_h)
+                    .map(|cy| (0..draw_w).map(|cx| cell_style(cx, cy)).collect())
+                    .collect()
+            });
+        let longest_run = flow_cells.iter().flatten().map(|row| {
+            row.split(|cell| cell.is_none())
+                .map(<[_]>::len)
+                .max()
+                .unwrap_or(0)
+        });
+        let longest_run = longest_run.max().unwrap_or(0);
+
+        let scroll_offset = (scroll_y as usize) * term_w;
+        let mut text_idx = scroll_offset % dense_text.len();
+        let next_char = |text_idx: &mut usize| {
+            let ch = dense_text[*text_idx];
+            *text_idx = (*text_idx + 1) % dense_text.len();
+            ch
+        };
 
-    let draw_w = (src_w as f64 * scale) as usize;
-    let draw_h = (src_h as f64 * scale) as usize;
+        for y in 0..term_h {
+            let mut x = 0;
 
-    let off_x = (term_w.saturating_sub(draw_w)) / 2;
-    let off_y = (term_h.saturating_sub(draw_h)) / 2;
+            while x < term_w {
+                let inside_video =
+                    x >= off_x && x < off_x + draw_w && y >= off_y && y < off_y + draw_h;
 
-    let mut lines = Vec::with_capacity(term_h);
-    let scroll_offset = (app.scroll_y as usize) * term_w;
-    let mut text_idx = scroll_offset % app.dense_text.len().max(1);
+                if !inside_video {
+                    let ch = next_char(&mut text_idx);
+                    let w = UnicodeWidthChar::width(ch).unwrap_or(1);
+                    if x + w <= term_w {
+                        put(buf, area, (x, y), Some((ch, theme.surround)));
+                    }
+                    x += w;
+                    continue;
+                }
 
-    for y in 0..term_h {
-        let mut spans = Vec::with_capacity(term_w);
-        let mut x = 0;
+                if let Some(flow_cells) = &flow_cells {
+                    let row = &flow_cells[y - off_y];
+                    let lit: Vec<bool> = row.iter().map(Option::is_some).collect();
+                    let words = flow_words(&lit, dense_text, &mut text_idx, longest_run);
+                    let mut cx = x - off_x;
+                    while cx < draw_w {
+                        let Some((cluster, style)) = words[cx].zip(row[cx]) else {
+                            put(buf, area, (off_x + cx, y), None);
+                            cx += 1;
+                            continue;
+                        };
+                        put(buf, area, (off_x + cx, y), Some((cluster[0], style)));
+                        if cluster.len() > 1 {
+                            let symbol: String = cluster.iter().collect();
+                            let (x, y) = (area.x + (off_x + cx) as u16, area.y + y as u16);
+                            buf[(x, y)].set_symbol(&symbol);
+                        }
+                        cx += cluster_width(cluster);
+                    }
+                    x += draw_w;
+                    continue;
+                }
 
-        while x < term_w {
-            let inside_video = x >= off_x && x < off_x + draw_w && y >= off_y && y < off_y + draw_h;
+                if matches!(render_mode, RenderMode::HalfBlock | RenderMode::Braille) {
+                    let cell = (x - off_x, y - off_y);
+                    let glyph = sub_cell(frame, render_mode, theme, cell, (draw_w, draw_h));
+                    put(buf, area, (x, y), glyph);
+                    x += 1;
+                    continue;
+                }
 
-            if !inside_video {
-                let ch = app.dense_text[text_idx];
+                let ch = dense_text[text_idx];
                 let w = UnicodeWidthChar::width(ch).unwrap_or(1);
-                if x + w <= term_w {
-                    spans.push(Span::styled(
-                        ch.to_string(),
-                        Style::default().fg(Color::DarkGray),
-                    ));
-                }
-                x += w;
-                text_idx = (text_idx + 1) % app.dense_text.len().max(1);
-                continue;
-            }
 
-            let src_x = ((x - off_x) * src_w) / draw_w;
-            let src_y = ((y - off_y) * src_h) / draw_h;
-
-            let sx = src_x.min(src_w - 1);
-            let sy = src_y.min(src_h - 1);
-            let pixel_idx = (sy * src_w + sx).min(buf.len() - 1);
-            let brightness = buf[pixel_idx];
-
-            let ch = app.dense_text[text_idx];
-            let w = UnicodeWidthChar::width(ch).unwrap_or(1);
-
-            if x + w <= term_w {
-                let (fg, bg, modifier) = match brightness {
-                    0..=30 => (Color::Black, Color::Black, Modifier::empty()),
-                    31..=100 => (Color::DarkGray, Color::Black, Modifier::DIM),
-                    101..=200 => (Color::White, Color::Black, Modifier::empty()),
-                    201..=255 => (Color::Black, Color::White, Modifier::BOLD),
-                };
-
-                match app.render_mode {
-                    RenderMode::Cast => {
-                        if bg == Color::Black && fg == Color::Black {
-                            spans.push(Span::raw(" ".repeat(w)));
-                        } else {
-                            spans.push(Span::styled(
-                                ch.to_string(),
-                                Style::default().fg(fg).bg(bg).add_modifier(modifier),
-                            ));
+                if let Some(gradients) = &gradients {
+                    // Text keeps flowing under the picture, lit up only on contours
+                    let sx = ((x - off_x) * src_w / draw_w).min(src_w - 1);
+                    let sy = ((y - off_y) * src_h / draw_h).min(src_h - 1);
+                    let gradient = gradients[sy * src_w + sx];
+                    if x + w <= term_w {
+                        match (gradient.is_edge(), render_mode) {
+                            (true, RenderMode::Outline) => {
+                                put(buf, area, (x, y), Some((gradient.glyph(), theme.pixel)));
+                                for i in 1..w {
+                                    put(buf, area, (x + i, y), Some((' ', theme.pixel)));
+                                }
+                            }
+                            (true, _) => put(buf, area, (x, y), Some((ch, theme.pixel))),
+                            (false, _) => put(buf, area, (x, y), Some((ch, theme.surround))),
                         }
-                        text_idx = (text_idx + 1) % app.dense_text.len().max(1);
                     }
-                    RenderMode::Fit => {
-                        if brightness > 50 {
-                            spans.push(Span::styled(
-                                ch.to_string(),
-                                Style::default().fg(fg).bg(bg).add_modifier(modifier),
-                            ));
-                            text_idx = (text_idx + 1) % app.dense_text.len().max(1);
-                        } else {
-                            spans.push(Span::raw(" ".repeat(w)));
+                    next_char(&mut text_idx);
+                    x += w;
+                    continue;
+                }
+
+                if x + w <= term_w {
+                    let style = cell_style(x - off_x, y - off_y);
+                    match (render_mode, style) {
+                        (RenderMode::Cast, Some(style)) => {
+                            put(buf, area, (x, y), Some((ch, style)));
+                            next_char(&mut text_idx);
+                        }
+                        (RenderMode::Cast, None) => {
+                            for i in 0..w {
+                                put(buf, area, (x + i, y), None);
+                            }
+                            next_char(&mut text_idx);
                         }
+                        (RenderMode::Fit, Some(style)) => {
+                            put(buf, area, (x, y), Some((ch, style)));
+                            next_char(&mut text_idx);
+                        }
+                        (RenderMode::Fit, _) => {
+                            for i in 0..w {
+                                put(buf, area, (x + i, y), None);
+                            }
+                        }
+                        (
+                            RenderMode::Flow
+                            | RenderMode::HalfBlock
+                            | RenderMode::Braille
+                            | RenderMode::Edge
+                            | RenderMode::Outline,
+                            _,
+                        ) => unreachable!("drawn above"),
                     }
                 }
+                x += w;
             }
-            x += w;
         }
-        lines.push(Line::from(spans));
     }
-    f.render_widget(Paragraph::new(lines), area);
 }
@@ -2,13 +2,23 @@ use chrono::{DateTime, Local};
 use percent_encoding::percent_decode_str;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::PathBuf;
+
+/// Written to the working directory by the app; tests leave it alone.
+const LOG_FILE: &str = "bad-browser.log";
+
+/// Starts a fresh log for this run.
+pub fn clear_log() {
+    if !cfg!(test) {
+        let _ = std::fs::write(LOG_FILE, "");
+    }
+}
 
 pub fn log_msg(level: &str, msg: &str) {
-    if let Ok(mut file) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("bad-browser.log")
-    {
+    if cfg!(test) {
+        return;
+    }
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(LOG_FILE) {
         let now: DateTime<Local> = Local::now();
         let _ = writeln!(
             file,
@@ -18,6 +28,77 @@ pub fn log_msg(level: &str, msg: &str) {
     }
 }
 
+pub fn parse_timestamp(s: &str) -> anyhow::Result<f64> {
+    // Try MM:SS.ms format first
+    if s.contains(':') {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() == 2 {
+            let minutes: f64 = parts[0].parse()?;
+            let seconds: f64 = parts[1].parse()?;
+            return Ok(minutes * 60.0 + seconds);
+        }
+    }
+    // Fall back to plain seconds
+    Ok(s.parse()?)
+}
+
+/// Inverse of `parse_timestamp`, as `MM:SS.ss`.
+pub fn format_timestamp(seconds: f64) -> String {
+    let seconds = seconds.max(0.0);
+    let minutes = (seconds / 60.0).floor();
+    format!("{:02}:{:05.2}", minutes as u64, seconds - minutes * 60.0)
+}
+
 pub fn decode_url(input: &str) -> String {
     percent_decode_str(input).decode_utf8_lossy().to_string()
 }
+
+/// `$XDG_DATA_HOME/bad-browser`, falling back to `~/.local/share/bad-browser`.
+pub fn data_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("bad-browser")
+}
+
+/// `$XDG_CONFIG_HOME/bad-browser`, falling back to `~/.config/bad-browser`.
+pub fn config_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("bad-browser")
+}
+
+pub fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn unescape_html(input: &str) -> String {
+    input
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#039;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatted_timestamps_parse_back() {
+        assert_eq!(format_timestamp(83.5), "01:23.50");
+        assert_eq!(parse_timestamp(&format_timestamp(83.5)).unwrap(), 83.5);
+        assert_eq!(parse_timestamp("12.25").unwrap(), 12.25);
+    }
+}
@@ -1,206 +1,233 @@
+use crate::audio::{AudioBackend, AudioConfig, NullAudio, open_backend};
+use crate::clock::PlaybackClock;
+use crate::color::ColorDepth;
+use crate::frame::FrameBuffer;
+use crate::source::{FrameSource, SourceSpec};
 use crate::types::BgEvent;
 use crate::utils::log_msg;
-use std::io::Read;
 use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+/// A late frame is still shown after this many drops in a row, so a decoder that can't keep
+/// up degrades to a lower frame rate instead of a frozen picture.
+const MAX_CONSECUTIVE_DROPS: u32 = 5;
+/// Upper bound on one sleep while waiting for an early frame, to stay responsive to
+/// pause, stop and rate changes.
+const MAX_WAIT: f64 = 0.05;
+/// Width of a terminal cell divided by its height, typical of monospace fonts.
+pub const DEFAULT_CELL_ASPECT: f64 = 0.5;
+
+/// Playback settings fixed for the whole run.
+pub struct VideoOptions {
+    pub audio: AudioConfig,
+    /// Width of a terminal cell divided by its height
+    pub cell_aspect: f64,
+    /// Decode rgb24 and color the text at this depth instead of gray
+    pub color: Option<ColorDepth>,
+}
 
 pub struct VideoEngine {
-    pub buffer: Arc<Mutex<Vec<u8>>>,
-    pub source_width: Arc<Mutex<usize>>,
-    pub source_height: Arc<Mutex<usize>>,
+    /// Latest frame from the playback thread, with the size it was decoded at
+    pub frames: Arc<FrameBuffer>,
 
     pub current_stopper: Option<Arc<AtomicBool>>,
-    pub pause_signal: Arc<AtomicBool>,
 
-    pub audio_process: Option<Child>,
-    pub ffmpeg_process: Option<Child>,
+    audio: Box<dyn AudioBackend>,
+    audio_config: AudioConfig,
 
-    pub seek_time: f64,
+    pub clock: PlaybackClock,
     pub duration: f64,
-    pub start_instant: Instant,
     pub session_id: usize,
-    pub is_paused: bool,
-
-    video_path: String,
+    /// Terminal size passed to the last `start`, for restarts that don't come from a key
+    pub term_size: (usize, usize),
+
+    /// Cell width over cell height, for decoding at the source's true proportions
+    cell_aspect: f64,
+    /// Pixels per cell the render mode can show, to decode at that resolution
+    cell_pixels: (usize, usize),
+    pub color: Option<ColorDepth>,
+    native_size: Option<(usize, usize)>,
+    source: SourceSpec,
     tx: std::sync::mpsc::SyncSender<BgEvent>,
 }
 
 impl VideoEngine {
-    pub fn new(video_path: String, tx: std::sync::mpsc::SyncSender<BgEvent>) -> Self {
-        let duration = Self::get_video_duration(&video_path).unwrap_or(0.0);
+    pub fn new(
+        video_path: &str,
+        options: &VideoOptions,
+        tx: std::sync::mpsc::SyncSender<BgEvent>,
+    ) -> Self {
+        let (source, native_size, audio) =
+            open_video(video_path, &options.audio, options.color.is_some());
         Self {
-            buffer: Arc::new(Mutex::new(Vec::new())),
-            source_width: Arc::new(Mutex::new(100)),
-            source_height: Arc::new(Mutex::new(50)),
+            frames: Arc::default(),
             current_stopper: None,
-            pause_signal: Arc::new(AtomicBool::new(false)),
-            audio_process: None,
-            ffmpeg_process: None,
-            seek_time: 0.0,
-            duration,
-            start_instant: Instant::now(),
+            audio,
+            audio_config: options.audio.clone(),
+            clock: PlaybackClock::default(),
+            duration: source.duration().unwrap_or(0.0),
             session_id: 0,
-            is_paused: false,
-            video_path,
+            term_size: (100, 50),
+            cell_aspect: options.cell_aspect,
+            cell_pixels: (1, 1),
+            color: options.color,
+            native_size,
+            source,
             tx,
         }
     }
 
+    /// Stops playback and switches to another video, for playlists.
+    pub fn load(&mut self, video_path: &str) {
+        self.stop();
+        let (source, native_size, audio) =
+            open_video(video_path, &self.audio_config, self.color.is_some());
+        self.audio = audio;
+        self.duration = source.duration().unwrap_or(0.0);
+        self.native_size = native_size;
+        self.source = source;
+        log_msg(
+            "info",
+            &format!("Loaded {video_path} ({:.2}s)", self.duration),
+        );
+    }
+
     pub fn start(&mut self, term_w: usize, term_h: usize, seek_seconds: f64) {
         log_msg("info", "Starting Video...");
+        self.audio.start(seek_seconds);
+        self.clock.set_position(seek_seconds);
+        self.clock.set_paused(false);
+        self.spawn_pipeline(term_w, term_h, seek_seconds);
+    }
+
+    /// Rebuilds only the frame pipeline for a new terminal size, continuing from the
+    /// current clock position. Audio keeps playing and the old frame stays on screen
+    /// until the first new one is ready.
+    pub fn resize(&mut self, term_w: usize, term_h: usize) {
+        if self.current_stopper.is_none() || self.term_size == (term_w, term_h) {
+            return;
+        }
+        log_msg("info", &format!("Resizing video to {term_w}x{term_h}"));
+        self.spawn_pipeline(term_w, term_h, self.clock.position());
+    }
+
+    /// Decodes at `cell_pixels` frame pixels per terminal cell from now on, restarting the
+    /// frame pipeline like `resize` if the video is playing.
+    pub fn set_cell_pixels(&mut self, cell_pixels: (usize, usize)) {
+        if self.cell_pixels == cell_pixels {
+            return;
+        }
+        self.cell_pixels = cell_pixels;
+        if self.current_stopper.is_some() {
+            let (term_w, term_h) = self.term_size;
+            self.spawn_pipeline(term_w, term_h, self.clock.position());
+        }
+    }
+
+    fn spawn_pipeline(&mut self, term_w: usize, term_h: usize, seek_seconds: f64) {
         self.stop_processes();
 
         self.session_id += 1;
         let current_session_id = self.session_id;
+        self.term_size = (term_w, term_h);
 
         let new_stopper = Arc::new(AtomicBool::new(false));
         self.current_stopper = Some(new_stopper.clone());
 
-        self.is_paused = false;
-        self.pause_signal.store(false, Ordering::Relaxed);
-
-        self.spawn_audio(seek_seconds);
-
-        self.seek_time = seek_seconds;
-        self.start_instant = Instant::now();
-
-        let buf = self.buffer.clone();
-        let w = self.source_width.clone();
-        let h = self.source_height.clone();
-        let path = self.video_path.clone();
         let tx = self.tx.clone();
-        let pause_sig = self.pause_signal.clone();
-
-        *w.lock().unwrap() = term_w;
-        *h.lock().unwrap() = term_h;
-
-        {
-            let mut lock = buf.lock().unwrap();
-            *lock = vec![0u8; term_w * term_h];
-        }
+        let clock = self.clock.clone();
+
+        let (cells_w, cells_h) = self.native_size.map_or((term_w, term_h), |size| {
+            fit_frame((term_w, term_h), size, self.cell_aspect)
+        });
+        let (width, height) = (cells_w * self.cell_pixels.0, cells_h * self.cell_pixels.1);
+        let source = match self.source.open(
+            width,
+            height,
+            seek_seconds,
+            self.clock.rate(),
+            self.color.is_some(),
+        ) {
+            Ok(source) => source,
+            Err(e) => {
+                log_msg("error", &format!("Failed to open video source: {e:#}"));
+                return;
+            }
+        };
+        log_msg("info", "Frame source opened");
+
+        let output = FrameOutput {
+            frames: self.frames.clone(),
+            // Prerendered frames keep their own size at one pixel per cell
+            scale: if source.size() == (width, height) {
+                self.cell_pixels
+            } else {
+                (1, 1)
+            },
+        };
 
-        let seek_str = format!("{seek_seconds:.2}");
-        let ffmpeg_child = Command::new("ffmpeg")
-            .args(&[
-                "-ss",
-                &seek_str,
-                "-re",
-                "-i",
-                &path,
-                "-f",
-                "rawvideo",
-                "-pix_fmt",
-                "gray",
-                "-s",
-                &format!("{term_w}x{term_h}"),
-                "-v",
-                "quiet",
-                "-",
-            ])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn();
-
-        if let Ok(mut child) = ffmpeg_child {
-            log_msg("info", "FFmpeg process spawned");
-            let mut stdout = child.stdout.take().unwrap();
-            self.ffmpeg_process = Some(child);
-
-            thread::spawn(move || {
-                let size = term_w * term_h;
-                let mut frame = vec![0u8; size];
-                let stopper = new_stopper;
-
-                while !stopper.load(Ordering::Relaxed) {
-                    if pause_sig.load(Ordering::Relaxed) {
-                        thread::sleep(Duration::from_millis(100));
-                        continue;
-                    }
-
-                    match stdout.read_exact(&mut frame) {
-                        Ok(_) => {
-                            if stopper.load(Ordering::Relaxed) {
-                                break;
-                            }
-                            if !pause_sig.load(Ordering::Relaxed) {
-                                let mut lock = buf.lock().unwrap();
-                                if lock.len() == size {
-                                    lock.copy_from_slice(&frame);
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-                        Err(_) => {
-                            if !stopper.load(Ordering::Relaxed) {
-                                let _ = tx.send(BgEvent::VideoEnded(current_session_id));
-                            }
-                            break;
-                        }
-                    }
-                }
-                log_msg("info", &format!("Video Thread {current_session_id} Ended"));
-            });
-        }
+        thread::spawn(move || {
+            play(source, &output, &clock, &new_stopper);
+            if !new_stopper.load(Ordering::Relaxed) {
+                let _ = tx.send(BgEvent::VideoEnded(current_session_id));
+            }
+            let stats = clock.stats();
+            log_msg(
+                "info",
+                &format!(
+                    "Video Thread {current_session_id} Ended ({} shown, {} dropped, {} repeated)",
+                    stats.presented, stats.dropped, stats.repeated
+                ),
+            );
+        });
     }
 
     pub fn stop(&mut self) {
         self.stop_processes();
-        self.seek_time = 0.0;
-        self.is_paused = false;
-        self.pause_signal.store(false, Ordering::Relaxed);
+        self.audio.stop();
+        self.frames.clear();
+        self.clock.set_position(0.0);
+        self.clock.set_paused(false);
     }
 
     pub fn toggle_pause(&mut self) {
-        self.is_paused = !self.is_paused;
-        self.pause_signal.store(self.is_paused, Ordering::Relaxed);
-
-        if self.is_paused {
-            let elapsed = self.start_instant.elapsed().as_secs_f64();
-            self.seek_time += elapsed;
-
-            if let Some(child) = &self.audio_process {
-                let pid = child.id().to_string();
-                let status = Command::new("kill").arg("-STOP").arg(&pid).output();
-
-                if status.is_err() || !status.unwrap().status.success() {
-                    if let Some(mut c) = self.audio_process.take() {
-                        let _ = c.kill();
-                        let _ = c.wait();
-                    }
-                }
-            }
-        } else {
-            self.start_instant = Instant::now();
+        let paused = !self.clock.is_paused();
+        self.clock.set_paused(paused);
 
-            let mut need_respawn = true;
-            if let Some(child) = &self.audio_process {
-                let pid = child.id().to_string();
-                let status = Command::new("kill").arg("-CONT").arg(&pid).output();
-
-                if status.is_ok() && status.unwrap().status.success() {
-                    need_respawn = false;
-                }
-            }
+        if paused {
+            self.audio.pause();
+        } else {
+            self.audio.resume(self.clock.position());
+        }
+    }
 
-            if need_respawn {
-                self.spawn_audio(self.seek_time);
+    /// Changes the playback speed, restarting the video pipe and audio at the current
+    /// position so both are retimed.
+    pub fn set_rate(&mut self, rate: f64, term_w: usize, term_h: usize) {
+        let position = self.clock.position();
+        let paused = self.clock.is_paused();
+        self.clock.set_rate(rate);
+        self.audio.set_rate(rate);
+        log_msg("info", &format!("Playback rate {rate}x"));
+
+        if self.current_stopper.is_some() {
+            self.start(term_w, term_h, position);
+            if paused {
+                self.toggle_pause();
             }
         }
     }
 
     pub fn seek(&mut self, delta: f64, term_w: usize, term_h: usize) {
-        let elapsed = if self.is_paused {
-            0.0
-        } else {
-            self.start_instant.elapsed().as_secs_f64()
-        };
+        self.seek_to(self.clock.position() + delta, term_w, term_h);
+    }
 
-        let current_real_time = self.seek_time + elapsed;
-        let mut new_time = current_real_time + delta;
+    /// Restarts playback at `time`, clamped to the video.
+    pub fn seek_to(&mut self, time: f64, term_w: usize, term_h: usize) {
+        let mut new_time = time;
         if new_time < 0.0 {
             new_time = 0.0;
         }
@@ -217,66 +244,13 @@ impl VideoEngine {
         if let Some(stopper) = self.current_stopper.take() {
             stopper.store(true, Ordering::Relaxed);
         }
-
-        if let Some(mut child) = self.audio_process.take() {
-            let pid = child.id();
-            let _ = child.kill();
-            let _ = child.wait();
-            // Ensure it's really dead
-            let _ = Command::new("kill").arg("-9").arg(pid.to_string()).output();
-        }
-
-        if let Some(mut child) = self.ffmpeg_process.take() {
-            let pid = child.id();
-            let _ = child.kill();
-            let _ = child.wait();
-            // Ensure it's really dead
-            let _ = Command::new("kill").arg("-9").arg(pid.to_string()).output();
-        }
+        // The playback thread drops its FrameSource, and with it any ffmpeg child, as
+        // soon as it sees the stopper.
     }
 
-    fn spawn_audio(&mut self, seek_seconds: f64) {
-        if let Some(mut old) = self.audio_process.take() {
-            let pid = old.id();
-            let _ = old.kill();
-            let _ = old.wait();
-            // Ensure it's really dead
-            let _ = Command::new("kill").arg("-9").arg(pid.to_string()).output();
-        }
-
-        let seek_str = format!("{seek_seconds:.2}");
-
-        let child = Command::new("ffplay")
-            .args(&[
-                "-ss",
-                &seek_str,
-                "-nodisp",
-                "-autoexit",
-                "-hide_banner",
-                "-loglevel",
-                "panic",
-                "-fflags",
-                "nobuffer",
-                "-flags",
-                "low_delay",
-                "-analyzeduration",
-                "0",
-                "-probesize",
-                "32",
-                &self.video_path,
-            ])
-            .stdin(Stdio::null())
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .ok();
-
-        self.audio_process = child;
-    }
-
-    fn get_video_duration(path: &str) -> Option<f64> {
+    pub fn get_video_duration(path: &str) -> Option<f64> {
         let output = Command::new("ffprobe")
-            .args(&[
+            .args([
                 "-v",
                 "error",
                 "-show_entries",
@@ -291,3 +265,222 @@ impl VideoEngine {
         s.trim().parse::<f64>().ok()
     }
 }
+
+/// A parsed video source, its pixel size if known and its soundtrack.
+type OpenedVideo = (SourceSpec, Option<(usize, usize)>, Box<dyn AudioBackend>);
+
+/// Parses `video_path` into a frame source and opens its soundtrack.
+fn open_video(video_path: &str, audio: &AudioConfig, color: bool) -> OpenedVideo {
+    let source = SourceSpec::parse(video_path).unwrap_or_else(|e| {
+        log_msg("error", &format!("Bad video source {video_path}: {e:#}"));
+        SourceSpec::ffmpeg(video_path)
+    });
+    source.decodes_color(color);
+    let native_size = source.native_size();
+    if let Some((w, h)) = native_size {
+        log_msg("info", &format!("Video size: {w}x{h}"));
+    }
+    let audio: Box<dyn AudioBackend> = if source.has_audio() {
+        open_backend(audio, video_path)
+    } else {
+        Box::new(NullAudio)
+    };
+    (source, native_size, audio)
+}
+
+/// Largest frame in cells that fits `term` and, with cells `cell_aspect` times as wide as
+/// they are tall, shows a `source` sized picture undistorted. `render_mask` centers it.
+pub fn fit_frame(term: (usize, usize), source: (usize, usize), cell_aspect: f64) -> (usize, usize) {
+    let (term_w, term_h) = (term.0.max(1) as f64, term.1.max(1) as f64);
+    // Source width per source height, measured in cells
+    let aspect = source.0 as f64 / (source.1 as f64 * cell_aspect);
+    let (w, h) = if term_w / term_h > aspect {
+        (term_h * aspect, term_h)
+    } else {
+        (term_w, term_w / aspect)
+    };
+    ((w.round() as usize).max(1), (h.round() as usize).max(1))
+}
+
+/// Where one pipeline publishes its frames.
+struct FrameOutput {
+    frames: Arc<FrameBuffer>,
+    /// Pixels per cell of this pipeline's frames
+    scale: (usize, usize),
+}
+
+impl FrameOutput {
+    fn present(&self, frame: &[u8], width: usize, height: usize) {
+        self.frames.publish(frame, width, height, self.scale);
+    }
+}
+
+/// Presents frames from `source` into `output` when `clock` reaches their timestamps, until
+/// the stream ends or `stopper` is set. Frames that are already a full interval late are
+/// dropped; while the next frame is early the previous one stays on screen.
+fn play(
+    mut source: Box<dyn FrameSource>,
+    output: &FrameOutput,
+    clock: &PlaybackClock,
+    stopper: &AtomicBool,
+) {
+    let (w, h) = source.size();
+    let interval = 1.0 / source.fps();
+    let mut frame = vec![0u8; w * h * source.channels()];
+    let mut pending = None;
+    let mut consecutive_drops = 0;
+    let mut last_shown: Option<f64> = None;
+    let mut shown_any = false;
+
+    while !stopper.load(Ordering::Relaxed) {
+        // A pipeline restarted while paused (e.g. on resize) still shows its first frame
+        let paused = clock.is_paused();
+        if paused && shown_any {
+            thread::sleep(Duration::from_millis(10));
+            last_shown = None;
+            continue;
+        }
+
+        let pts = match pending.take() {
+            Some(pts) => pts,
+            None => match source.next_frame(&mut frame) {
+                Ok(Some(pts)) => {
+                    clock.record_decoded();
+                    pts
+                }
+                Ok(None) => return,
+                Err(e) => {
+                    log_msg("error", &format!("Frame source failed: {e:#}"));
+                    return;
+                }
+            },
+        };
+
+        let now = clock.position();
+        if paused {
+            output.present(&frame, w, h);
+            shown_any = true;
+            continue;
+        }
+        if pts + interval < now && consecutive_drops < MAX_CONSECUTIVE_DROPS {
+            consecutive_drops += 1;
+            clock.record_dropped();
+            continue;
+        }
+        if pts > now {
+            pending = Some(pts);
+            let wait = ((pts - now) / clock.rate()).min(MAX_WAIT);
+            thread::sleep(Duration::from_secs_f64(wait));
+            continue;
+        }
+
+        output.present(&frame, w, h);
+        shown_any = true;
+        // Every whole interval since the last frame showed that frame again
+        let repeated = last_shown
+            .map_or(0, |t| ((now - t) / interval) as u64)
+            .saturating_sub(1);
+        clock.record_presented(now - pts, repeated);
+        last_shown = Some(now);
+        consecutive_drops = 0;
+    }
+}
+
+/// Spawns ffmpeg decoding `path` into raw `width x height` frames of `pix_fmt` (`gray` or
+/// `rgb24`) on stdout, as fast as the pipe is drained. `speed` retimes the stream with `setpts` before `fps`
+/// resamples it to a fixed rate.
+pub fn spawn_decoder(
+    path: &str,
+    width: usize,
+    height: usize,
+    seek_seconds: f64,
+    speed: f64,
+    fps: Option<f64>,
+    pix_fmt: &str,
+) -> std::io::Result<Child> {
+    let mut args = vec!["-ss".to_string(), format!("{seek_seconds:.2}")];
+    args.extend(["-i".to_string(), path.to_string()]);
+    if speed != 1.0 {
+        args.extend(["-vf".to_string(), format!("setpts=PTS/{speed}")]);
+    }
+    if let Some(fps) = fps {
+        args.extend(["-r".to_string(), fps.to_string()]);
+    }
+    args.extend(
+        [
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            pix_fmt,
+            "-s",
+            &format!("{width}x{height}"),
+            "-v",
+            "quiet",
+            "-",
+        ]
+        .map(String::from),
+    );
+
+    Command::new("ffmpeg")
+        .args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioKind;
+    use std::sync::mpsc;
+
+    #[test]
+    fn plays_synthetic_source_without_ffmpeg() {
+        let (tx, rx) = mpsc::sync_channel(5);
+        let mut engine = VideoEngine::new(
+            "synthetic:circle:0.3",
+            &VideoOptions {
+                audio: AudioConfig {
+                    kind: AudioKind::Null,
+                    command: None,
+                },
+                cell_aspect: DEFAULT_CELL_ASPECT,
+                color: None,
+            },
+            tx,
+        );
+        assert_eq!(engine.duration, 0.3);
+
+        engine.start(16, 8, 0.0);
+        engine.seek(0.1, 16, 8);
+        assert_eq!(engine.session_id, 2);
+
+        // Same size is a no-op; a new size restarts only the frame pipeline
+        engine.resize(16, 8);
+        assert_eq!(engine.session_id, 2);
+        engine.resize(20, 10);
+        assert_eq!(engine.session_id, 3);
+
+        // Only the latest session reports its end
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(BgEvent::VideoEnded(id)) => assert_eq!(id, 3),
+            _ => panic!("expected VideoEnded"),
+        }
+        {
+            let frame = engine.frames.latest();
+            assert_eq!(frame.pixels.len(), 20 * 10);
+            assert_eq!(frame.width, 20);
+            assert!(frame.pixels.contains(&255));
+        }
+        engine.stop();
+    }
+
+    #[test]
+    fn fits_frame_to_source_and_cell_aspect() {
+        // 4:3 in half-width cells is 8:3: height-bound in 80x24, width-bound in 80x40
+        assert_eq!(fit_frame((80, 24), (640, 480), 0.5), (64, 24));
+        assert_eq!(fit_frame((80, 40), (640, 480), 0.5), (80, 30));
+        // Square cells keep the pixel aspect
+        assert_eq!(fit_frame((100, 100), (1920, 1080), 1.0), (100, 56));
+    }
+}
@@ -1,18 +1,45 @@
-use crate::types::BgEvent;
-use crate::utils::{decode_url, log_msg};
+use crate::images;
+use crate::types::{BgEvent, ImageRef, image_marker};
+use crate::utils::{decode_url, log_msg, unescape_html};
+use anyhow::{Result, bail};
 use regex::{Captures, Regex};
 use reqwest::Url;
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, Response};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::SyncSender;
 use std::thread;
 use std::time::Duration;
 
 const USER_AGENT: &str = "bad-browser/1.0";
+/// Width html2text wraps pages to; inline images are scaled to fit it.
+pub const TEXT_WIDTH: usize = 120;
+/// Images fetched per page; the rest are shown by their caption only.
+pub const MAX_IMAGES_PER_PAGE: usize = 16;
 
 pub struct WebEngine {
     client: Client,
     tx: SyncSender<BgEvent>,
+    /// Page and image downloads still running
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// Counts one download in `WebEngine::in_flight` until its thread finishes.
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn start(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 impl WebEngine {
@@ -22,15 +49,33 @@ impl WebEngine {
             .timeout(Duration::from_secs(10))
             .build()
             .unwrap();
-        Self { client, tx }
+        Self {
+            client,
+            tx,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Number of page and image downloads still running.
+    pub fn pending(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
     }
 
-    pub fn fetch(&self, current_url: &str, target: String, is_prefetch: bool, is_history: bool) {
+    pub fn fetch(
+        &self,
+        buffer_id: usize,
+        current_url: &str,
+        target: String,
+        is_prefetch: bool,
+        is_history: bool,
+    ) {
         let client = self.client.clone();
         let tx = self.tx.clone();
         let base_str = current_url.to_string();
+        let in_flight = InFlight::start(&self.in_flight);
 
         thread::spawn(move || {
+            let _in_flight = in_flight;
             let base = Url::parse(&base_str).ok();
             let target_url = match base {
                 Some(b) => b.join(&target).map(|u| u.to_string()).unwrap_or(target),
@@ -41,47 +86,209 @@ impl WebEngine {
                 log_msg("info", &format!("Fetching URL: {target_url}"));
             }
 
-            match client.get(&target_url).send() {
-                Ok(resp) => {
-                    if resp.status().is_success() {
-                        let html = resp.text().unwrap_or_default();
-                        let (text, dense, map, links) = parse_html(&html);
-
-                        let event = if is_prefetch {
-                            BgEvent::PrefetchReady {
-                                url: target_url,
-                                text,
-                                dense_text: dense,
-                                link_map: map,
-                                links,
-                            }
-                        } else {
-                            BgEvent::PageLoaded {
-                                url: target_url,
-                                text,
-                                dense_text: dense,
-                                link_map: map,
-                                links,
-                                is_history_nav: is_history,
-                            }
-                        };
-                        let _ = tx.send(event);
-                    } else if !is_prefetch {
-                        let status = resp.status();
-                        let _ = tx.send(BgEvent::Error(format!("HTTP {status}")));
-                    }
+            match download(&client, &target_url) {
+                Ok(html) => {
+                    let Page {
+                        title,
+                        text,
+                        dense_text: dense,
+                        link_map: map,
+                        links,
+                        images,
+                    } = Page::build(&html);
+
+                    let event = if is_prefetch {
+                        BgEvent::PrefetchReady {
+                            buffer_id,
+                            url: target_url,
+                            title,
+                            text,
+                            dense_text: dense,
+                            link_map: map,
+                            links,
+                            images,
+                        }
+                    } else {
+                        BgEvent::PageLoaded {
+                            buffer_id,
+                            url: target_url,
+                            title,
+                            text,
+                            dense_text: dense,
+                            link_map: map,
+                            links,
+                            images,
+                            is_history_nav: is_history,
+                        }
+                    };
+                    let _ = tx.send(event);
                 }
                 Err(e) => {
                     if !is_prefetch {
-                        let _ = tx.send(BgEvent::Error(e.to_string()));
+                        let _ = tx.send(BgEvent::Error(buffer_id, e.to_string()));
                     }
                 }
             }
         });
     }
+
+    /// Fetches a page on the calling thread, returning the final URL and its HTML.
+    /// `file://` URLs are read from disk so offline runs stay reproducible.
+    pub fn fetch_blocking(&self, url: &str) -> Result<(String, String)> {
+        if let Some(path) = url.strip_prefix("file://") {
+            return Ok((url.to_string(), std::fs::read_to_string(path)?));
+        }
+        let resp = self.client.get(url).send()?;
+        let final_url = resp.url().to_string();
+        Ok((final_url, read_html(resp)?))
+    }
+
+    pub fn fetch_image(&self, buffer_id: usize, page_url: &str, index: usize, image: ImageRef) {
+        let client = self.client.clone();
+        let tx = self.tx.clone();
+        let page_url = page_url.to_string();
+        let in_flight = InFlight::start(&self.in_flight);
+
+        thread::spawn(move || {
+            let _in_flight = in_flight;
+            let result = Url::parse(&page_url)
+                .and_then(|base| base.join(&image.src))
+                .map_err(anyhow::Error::from)
+                .and_then(|url| Ok(client.get(url).send()?.error_for_status()?.bytes()?))
+                .and_then(|bytes| images::decode_gray(bytes.to_vec(), TEXT_WIDTH));
+
+            let art = match result {
+                Ok(gray) => images::to_ascii(&gray),
+                Err(e) => {
+                    log_msg("warn", &format!("Image {src} failed: {e}", src = image.src));
+                    Vec::new()
+                }
+            };
+            let _ = tx.send(BgEvent::ImageReady {
+                buffer_id,
+                page_url,
+                index,
+                art,
+                alt: image.alt,
+            });
+        });
+    }
+}
+
+fn download(client: &Client, url: &str) -> Result<String> {
+    read_html(client.get(url).send()?)
+}
+
+fn read_html(resp: Response) -> Result<String> {
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("HTTP {status}");
+    }
+    Ok(resp.text().unwrap_or_default())
 }
 
-fn parse_html(html: &str) -> (String, Vec<char>, HashMap<String, String>, Vec<String>) {
+/// Swaps the first `MAX_IMAGES_PER_PAGE` `<img>`s for a text marker so the art can be
+/// spliced in once it's decoded, and any others for their caption.
+pub fn extract_images(html: &str) -> (Cow<'_, str>, Vec<ImageRef>) {
+    let img_regex = Regex::new(r"(?is)<img\b([^>]*)>").unwrap();
+    let src_regex = Regex::new(r#"(?is)\bsrc\s*=\s*["']([^"']+)["']"#).unwrap();
+    let alt_regex = Regex::new(r#"(?is)\balt\s*=\s*["']([^"']*)["']"#).unwrap();
+
+    let mut images = Vec::new();
+    let replaced = img_regex.replace_all(html, |caps: &Captures| {
+        let attrs = &caps[1];
+        let Some(src) = src_regex.captures(attrs) else {
+            return String::new();
+        };
+        let alt = alt_regex.captures(attrs);
+        if images.len() == MAX_IMAGES_PER_PAGE {
+            // Still escaped, as it goes back into the HTML
+            let alt = alt.as_ref().map_or("", |c| c.get(1).unwrap().as_str());
+            return format!("<br>{}<br>", image_caption(alt));
+        }
+        let alt = alt.map(|c| unescape_html(&c[1])).unwrap_or_default();
+
+        let marker = image_marker(images.len());
+        images.push(ImageRef {
+            src: unescape_html(&src[1]),
+            alt,
+        });
+        format!("<br>{marker}<br>")
+    });
+
+    (replaced, images)
+}
+
+/// How an image is shown when there is no art for it.
+pub fn image_caption(alt: &str) -> String {
+    let alt = if alt.is_empty() { "image" } else { alt };
+    format!("[{alt}]")
+}
+
+/// `text` with the marker of every image in `images` replaced by its caption.
+pub fn caption_images(text: &str, images: &[ImageRef]) -> String {
+    images
+        .iter()
+        .enumerate()
+        .fold(text.to_string(), |text, (index, image)| {
+            text.replace(&image_marker(index), &image_caption(&image.alt))
+        })
+}
+
+/// A downloaded page as the app shows it. Built the same way for live browsing and the
+/// offline renderers, so both mask the video with the same text.
+pub struct Page {
+    pub title: String,
+    pub text: String,
+    /// `text` without line breaks, with images as captions, for the video mask
+    pub dense_text: Vec<char>,
+    pub link_map: HashMap<String, String>,
+    pub links: Vec<String>,
+    pub images: Vec<ImageRef>,
+}
+
+impl Page {
+    pub fn build(html: &str) -> Self {
+        let title = extract_title(html);
+        let (html, images) = extract_images(html);
+        let (text, dense, link_map, links) = parse_html(&html, TEXT_WIDTH, LinkStyle::Hints);
+        // The mask flows prose around the video, so images stay captions there
+        let dense: String = dense.into_iter().collect();
+        let dense_text = caption_images(&dense, &images).chars().collect();
+        Self {
+            title,
+            text,
+            dense_text,
+            link_map,
+            links,
+            images,
+        }
+    }
+}
+
+pub fn extract_title(html: &str) -> String {
+    let title_regex = Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap();
+    title_regex
+        .captures(html)
+        .map(|caps| unescape_html(&caps[1]))
+        .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+        .unwrap_or_default()
+}
+
+/// How links are annotated in the rendered text.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LinkStyle {
+    /// `[href][key]`, where `key` is the two-letter hint typed after `f`.
+    Hints,
+    /// lynx-style `[n]text` pointing into a numbered reference list.
+    References,
+}
+
+pub fn parse_html(
+    html: &str,
+    width: usize,
+    style: LinkStyle,
+) -> (String, Vec<char>, HashMap<String, String>, Vec<String>) {
     let mut hint_gen = (0..).map(|i| {
         let a = (b'a' + (i % 26)) as char;
         let b = (b'a' + (i / 26)) as char;
@@ -95,10 +302,16 @@ fn parse_html(html: &str) -> (String, Vec<char>, HashMap<String, String>, Vec<St
     let injected = link_regex.replace_all(html, |caps: &Captures| {
         let raw_href = caps[1].to_string();
         let raw_text = &caps[2];
-        let key = hint_gen.next().unwrap();
+        valid_links.push(raw_href.clone());
 
+        if style == LinkStyle::References {
+            let n = valid_links.len();
+            link_map.insert(n.to_string(), raw_href.clone());
+            return format!("[{n}]{raw_text}");
+        }
+
+        let key = hint_gen.next().unwrap();
         link_map.insert(key.clone(), raw_href.clone());
-        valid_links.push(raw_href.clone());
 
         let display_href = decode_url(&raw_href);
         format!(
@@ -107,8 +320,50 @@ fn parse_html(html: &str) -> (String, Vec<char>, HashMap<String, String>, Vec<St
         )
     });
 
-    let text = html2text::from_read(injected.as_bytes(), 120).unwrap_or_default();
+    let text = html2text::from_read(injected.as_bytes(), width).unwrap_or_default();
     let dense: Vec<char> = text.chars().filter(|c| !c.is_control()).collect();
 
     (text, dense, link_map, valid_links)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_style_numbers_links_in_order() {
+        let html =
+            r#"<p>See <a href="/wiki/Reimu">Reimu</a> and <a href="/wiki/Marisa">Marisa</a></p>"#;
+        let (text, _, link_map, links) = parse_html(html, 80, LinkStyle::References);
+
+        assert_eq!(text.trim(), "See [1]Reimu and [2]Marisa");
+        assert_eq!(links, ["/wiki/Reimu", "/wiki/Marisa"]);
+        assert_eq!(link_map["2"], "/wiki/Marisa");
+    }
+
+    #[test]
+    fn images_past_the_limit_are_only_captioned() {
+        let html = (0..=MAX_IMAGES_PER_PAGE)
+            .map(|i| format!(r#"<img src="{i}.png" alt="Pic &amp; {i}">"#))
+            .collect::<String>();
+        let (replaced, images) = extract_images(&html);
+
+        assert_eq!(images.len(), MAX_IMAGES_PER_PAGE);
+        assert_eq!(images[0].alt, "Pic & 0");
+        assert!(replaced.contains(&image_marker(MAX_IMAGES_PER_PAGE - 1)));
+        assert!(!replaced.contains(&image_marker(MAX_IMAGES_PER_PAGE)));
+        assert!(replaced.contains(&format!("[Pic &amp; {MAX_IMAGES_PER_PAGE}]")));
+
+        let text = format!("{} {}", image_marker(0), image_marker(1));
+        assert_eq!(caption_images(&text, &images), "[Pic & 0] [Pic & 1]");
+    }
+
+    #[test]
+    fn built_pages_caption_images_only_in_the_mask_text() {
+        let page = Page::build(r#"<title>Apple</title><p>Red</p><img src="a.png" alt="fruit">"#);
+        let dense: String = page.dense_text.iter().collect();
+        assert_eq!(page.title, "Apple");
+        assert!(page.text.contains(&image_marker(0)));
+        assert!(dense.contains("[fruit]") && !dense.contains(&image_marker(0)));
+    }
+}